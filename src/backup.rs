@@ -0,0 +1,409 @@
+//! Whole-workspace backups in a single tar archive, and restoring them into a workspace.
+//! Only available with the `backup` feature.
+//!
+//! The archive holds json files with the same shapes the Nuclino API uses:
+//!
+//! - `workspace.json`: the `Workspace`
+//! - `tree.json`: every page id in depth-first order, with its parent
+//! - `pages/<id>.json`: each `Page`, with content for items
+//! - `files/<id>.json`: each attached `File`'s metadata
+//! - `files/<id>/<filename>`: each attached file's contents
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    remap_ids, Client, File, ModifyItem, NewPageBuilder, NuclinoError, NuclinoResult, Page,
+    PageTree, Workspace,
+};
+
+/// One page's place in the backed-up tree.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// The page's id.
+    pub id: Uuid,
+    /// The collection containing the page, or `None` for top-level pages.
+    pub parent: Option<Uuid>,
+}
+
+/// A file attached to a backed-up page, with its contents.
+#[derive(Debug, Clone)]
+pub struct BackupFile {
+    /// The file's metadata. The download link in it will have expired.
+    pub info: File,
+    /// The file's contents.
+    pub bytes: Vec<u8>,
+}
+
+/// A complete copy of a workspace: its pages with content, their order, and attachments.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    workspace: Workspace,
+    order: Vec<TreeEntry>,
+    pages: HashMap<Uuid, Page>,
+    files: Vec<BackupFile>,
+}
+
+impl Backup {
+    /// Make a backup from a hydrated tree and the files attached to its items.
+    pub fn from_tree(tree: &PageTree, files: Vec<BackupFile>) -> Self {
+        let order = tree
+            .depth_first()
+            .map(|node| TreeEntry {
+                id: *node.id(),
+                parent: node.parent().copied(),
+            })
+            .collect();
+        let pages = tree
+            .depth_first()
+            .map(|node| (*node.id(), node.page().clone()))
+            .collect();
+        Backup {
+            workspace: tree.workspace().clone(),
+            order,
+            pages,
+            files,
+        }
+    }
+
+    /// The backed-up workspace.
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspace
+    }
+
+    /// Every backed-up page, in depth-first tree order.
+    pub fn pages(&self) -> impl Iterator<Item = &Page> {
+        self.order
+            .iter()
+            .filter_map(|entry| self.pages.get(&entry.id))
+    }
+
+    /// The tree order of the backed-up pages.
+    pub fn order(&self) -> &[TreeEntry] {
+        self.order.as_slice()
+    }
+
+    /// Every backed-up attachment.
+    pub fn files(&self) -> &[BackupFile] {
+        self.files.as_slice()
+    }
+
+    /// Rebuild the `PageTree` this backup was made from.
+    pub fn tree(&self) -> NuclinoResult<PageTree> {
+        PageTree::build(
+            self.workspace.clone(),
+            self.pages.values().cloned().collect(),
+        )
+    }
+
+    /// Write this backup to a tar archive at `path`.
+    pub fn write(&self, path: &Path) -> NuclinoResult<()> {
+        let out = std::fs::File::create(path)?;
+        let mut archive = tar::Builder::new(out);
+        append(
+            &mut archive,
+            "workspace.json",
+            &serde_json::to_vec_pretty(&self.workspace)?,
+        )?;
+        append(
+            &mut archive,
+            "tree.json",
+            &serde_json::to_vec_pretty(&self.order)?,
+        )?;
+        for page in self.pages() {
+            let json = serde_json::to_vec_pretty(page)?;
+            append(&mut archive, &format!("pages/{}.json", page.id()), &json)?;
+        }
+        for file in &self.files {
+            let id = file.info.id();
+            append(
+                &mut archive,
+                &format!("files/{id}.json"),
+                &serde_json::to_vec_pretty(&file.info)?,
+            )?;
+            // File names come from users, so strip anything that could escape the directory.
+            let name = file.info.filename().replace(['/', '\\'], "_");
+            append(&mut archive, &format!("files/{id}/{name}"), &file.bytes)?;
+        }
+        archive.into_inner()?;
+        Ok(())
+    }
+
+    /// Read a backup from a tar archive written by `write()`.
+    pub fn read(path: &Path) -> NuclinoResult<Self> {
+        let mut archive = tar::Archive::new(std::fs::File::open(path)?);
+        let mut workspace: Option<Workspace> = None;
+        let mut order: Option<Vec<TreeEntry>> = None;
+        let mut pages: HashMap<Uuid, Page> = HashMap::new();
+        let mut infos: HashMap<Uuid, File> = HashMap::new();
+        let mut contents: HashMap<Uuid, Vec<u8>> = HashMap::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let mut bytes: Vec<u8> = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            let parts: Vec<&str> = name.split('/').collect();
+            match parts.as_slice() {
+                ["workspace.json"] => workspace = Some(serde_json::from_slice(&bytes)?),
+                ["tree.json"] => order = Some(serde_json::from_slice(&bytes)?),
+                ["pages", _] => {
+                    let page: Page = serde_json::from_slice(&bytes)?;
+                    pages.insert(*page.id(), page);
+                }
+                ["files", _] => {
+                    let info: File = serde_json::from_slice(&bytes)?;
+                    infos.insert(*info.id(), info);
+                }
+                ["files", id, _] => {
+                    if let Ok(id) = Uuid::try_parse(id) {
+                        contents.insert(id, bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (Some(workspace), Some(order)) = (workspace, order) else {
+            return Err(NuclinoError::NotFound(format!(
+                "workspace.json and tree.json in backup {}",
+                path.display()
+            )));
+        };
+        let files = infos
+            .into_iter()
+            .filter_map(|(id, info)| contents.remove(&id).map(|bytes| BackupFile { info, bytes }))
+            .collect();
+        Ok(Backup {
+            workspace,
+            order,
+            pages,
+            files,
+        })
+    }
+}
+
+fn append<W: std::io::Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> NuclinoResult<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+impl Client {
+    /// Back up an entire workspace, with page content and attachments, to a tar archive at
+    /// `path`. This makes one request per item and two per attachment, so it can be slow.
+    pub fn backup_workspace(&self, workspace: &Uuid, path: &Path) -> NuclinoResult<Backup> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        let mut files: Vec<BackupFile> = Vec::new();
+        for node in tree.depth_first() {
+            if let Page::Item(item) = node.page() {
+                for (info, bytes) in self.download_attachments(item)? {
+                    files.push(BackupFile { info, bytes });
+                }
+            }
+        }
+        let backup = Backup::from_tree(&tree, files);
+        backup.write(path)?;
+        Ok(backup)
+    }
+
+    /// Recreate a backup's pages in a workspace, in their original order, appended after
+    /// anything already there. Links between restored pages are rewritten to point at the
+    /// new copies. Attachments can't be restored, because the API has no way to upload
+    /// files; links to them still point at the originals. Returns a map from each old
+    /// page id to its new one.
+    ///
+    /// Untitled pages are restored untitled. Every page is checked before any are created,
+    /// so a page Nuclino would reject stops the restore with `NuclinoError::InvalidNewPage`
+    /// and nothing is written. If a request fails after that, the error is
+    /// `NuclinoError::PartialRestore`, carrying the map of pages created so far.
+    pub fn restore_into(
+        &self,
+        backup: &Backup,
+        workspace: &Uuid,
+    ) -> NuclinoResult<HashMap<Uuid, Uuid>> {
+        for entry in backup.order() {
+            if let Some(page) = backup.pages.get(&entry.id) {
+                let mut builder = restore_builder(page);
+                builder.workspace(workspace).try_build()?;
+            }
+        }
+
+        let mut ids: HashMap<Uuid, Uuid> = HashMap::new();
+        match self.restore_pages(backup, workspace, &mut ids) {
+            Ok(()) => Ok(ids),
+            Err(source) => Err(NuclinoError::PartialRestore {
+                restored: ids,
+                source: Box::new(source),
+            }),
+        }
+    }
+
+    /// The part of `restore_into()` that talks to Nuclino, recording each new id in `ids`
+    /// as soon as the page exists.
+    fn restore_pages(
+        &self,
+        backup: &Backup,
+        workspace: &Uuid,
+        ids: &mut HashMap<Uuid, Uuid>,
+    ) -> NuclinoResult<()> {
+        for entry in backup.order() {
+            let Some(page) = backup.pages.get(&entry.id) else {
+                continue;
+            };
+            let mut builder = restore_builder(page);
+            match entry.parent.and_then(|parent| ids.get(&parent)) {
+                Some(parent) => builder.parent(parent),
+                None => builder.workspace(workspace),
+            };
+            let created = self.page_create(builder.try_build()?)?;
+            ids.insert(entry.id, *created.id());
+        }
+
+        // Now that every page has a new id, point links at the restored copies.
+        for page in backup.pages() {
+            let Page::Item(item) = page else {
+                continue;
+            };
+            let Some(content) = item.content() else {
+                continue;
+            };
+            if !item
+                .content_meta()
+                .item_ids
+                .iter()
+                .any(|id| ids.contains_key(id))
+            {
+                continue;
+            }
+            let Some(new_id) = ids.get(item.id()) else {
+                continue;
+            };
+            let changes = ModifyItem {
+                title: None,
+                content: Some(remap_ids(content, ids)),
+            };
+            self.page_update(new_id, &changes)?;
+        }
+        Ok(())
+    }
+}
+
+/// A builder for a copy of `page`, without a location. Nuclino allows untitled pages
+/// but `try_build()` rejects an empty title, so those are left unset.
+fn restore_builder(page: &Page) -> NewPageBuilder {
+    let mut builder = match page {
+        Page::Item(item) => {
+            let mut builder = NewPageBuilder::item();
+            builder.content(item.content().map(String::as_str).unwrap_or_default());
+            builder
+        }
+        Page::Collection(_) => NewPageBuilder::collection(),
+    };
+    if !page.title().trim().is_empty() {
+        builder.title(page.title());
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+    use crate::NewPageError;
+
+    #[test]
+    fn archive_round_trip() {
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2)]),
+            item(id(2), "Deploy", Some("# Deploy\n\nSee [Welcome](https://app.nuclino.com/t/b/00000000-0000-0000-0000-000000000003).")),
+            item(id(3), "Welcome", Some("Hello.")),
+        ];
+        let tree = PageTree::build(workspace(&[id(1), id(3)]), pages).expect("tree should build");
+        let info = file(id(10), id(2), "../diagram.png");
+        let backup = Backup::from_tree(
+            &tree,
+            vec![BackupFile {
+                info,
+                bytes: vec![1, 2, 3],
+            }],
+        );
+
+        let path = std::env::temp_dir().join(format!("nuclino-backup-{}.tar", std::process::id()));
+        backup
+            .write(&path)
+            .expect("writing the archive should work");
+        let restored = Backup::read(&path).expect("reading the archive should work");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(restored.workspace().id(), backup.workspace().id());
+        assert_eq!(restored.order(), backup.order());
+        let titles: Vec<&str> = restored.pages().map(|p| p.title()).collect();
+        assert_eq!(titles, vec!["Runbooks", "Deploy", "Welcome"]);
+        assert_eq!(restored.files().len(), 1);
+        assert_eq!(restored.files()[0].bytes, vec![1, 2, 3]);
+        let rebuilt = restored.tree().expect("the tree should rebuild");
+        assert_eq!(rebuilt.parent_of(&id(2)).map(|n| *n.id()), Some(id(1)));
+    }
+
+    #[test]
+    fn restoring_invalid_pages() {
+        let long = "x".repeat(crate::MAX_TITLE_LENGTH + 1);
+        let pages = vec![
+            item(id(1), "Fine", Some("Hello.")),
+            item(id(2), &long, None),
+        ];
+        let tree = PageTree::build(workspace(&[id(1), id(2)]), pages).expect("tree should build");
+        let backup = Backup::from_tree(&tree, Vec::new());
+        // Nothing listens here; every page is checked before any request is made.
+        let result = local_client("http://127.0.0.1:9").restore_into(&backup, &id(100));
+        assert!(matches!(
+            result,
+            Err(NuclinoError::InvalidNewPage(
+                NewPageError::TitleTooLong { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn restoring_untitled_pages() {
+        let pages = vec![item(id(1), "", Some("Hello.")), item(id(2), "Second", None)];
+        let tree = PageTree::build(workspace(&[id(1), id(2)]), pages).expect("tree should build");
+        let backup = Backup::from_tree(&tree, Vec::new());
+        let (url, server) = serve(vec![
+            (
+                200,
+                json!({ "status": "success", "data": item(id(11), "", Some("Hello.")) }),
+            ),
+            (
+                503,
+                json!({ "status": "error", "message": "Try again later" }),
+            ),
+        ]);
+        let result = local_client(&url).restore_into(&backup, &id(100));
+        let Err(NuclinoError::PartialRestore { restored, source }) = result else {
+            panic!("a failed create should report a partial restore: {result:?}");
+        };
+        assert_eq!(restored, HashMap::from([(id(1), id(11))]));
+        assert!(matches!(
+            *source,
+            NuclinoError::ServerError { status: 503, .. }
+        ));
+        let requests = server.join().expect("the server should finish");
+        assert_eq!(requests.len(), 2);
+    }
+}
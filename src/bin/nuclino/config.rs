@@ -0,0 +1,112 @@
+//! Finding credentials: the `NUCLINO_API_KEY` env var, or a named profile in a config file.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use nuclino_rs::{Client, NuclinoError, APIKEY_ENV_VAR};
+use serde::Deserialize;
+
+use crate::{CliError, CliResult};
+
+/// The env var that overrides where we look for the config file.
+pub static CONFIG_ENV_VAR: &str = "NUCLINO_CONFIG";
+
+/// The config file. Looks like this:
+///
+/// ```toml
+/// default = "work"
+///
+/// [profiles.work]
+/// api_key = "..."
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// The profile to use when none is named and the api key env var is unset.
+    pub default: Option<String>,
+    /// Named sets of credentials.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Credentials for one Nuclino account.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    /// The api key to use.
+    pub api_key: String,
+    /// An optional override for the api base url.
+    pub base_url: Option<String>,
+}
+
+/// Where the config file lives: `$NUCLINO_CONFIG`, or `nuclino/config.toml` in the
+/// XDG config directory.
+pub fn config_path() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var(CONFIG_ENV_VAR) {
+        return Some(PathBuf::from(path));
+    }
+    let base = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+    };
+    Some(base.join("nuclino").join("config.toml"))
+}
+
+impl Config {
+    /// Load the config file, treating a missing file as an empty config.
+    pub fn load() -> CliResult<Self> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        Config::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> CliResult<Self> {
+        toml::from_str(text).map_err(|e| CliError::Config(e.to_string()))
+    }
+}
+
+/// Make a client. A named profile wins; then the api key env var; then the config
+/// file's default profile.
+pub fn client(profile: Option<&str>) -> CliResult<Client> {
+    if profile.is_none() {
+        match std::env::var(APIKEY_ENV_VAR) {
+            Ok(key) if !key.trim().is_empty() => return Ok(Client::create(key.trim(), None)),
+            _ => {}
+        }
+    }
+    let config = Config::load()?;
+    let Some(name) = profile.map(str::to_string).or(config.default.clone()) else {
+        return Err(NuclinoError::ApiKeyNotFound.into());
+    };
+    let Some(found) = config.profiles.get(&name) else {
+        return Err(CliError::Config(format!(
+            "no profile named '{name}'; set {APIKEY_ENV_VAR} or add the profile to the config file"
+        )));
+    };
+    Ok(Client::create(&found.api_key, found.base_url.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let config = Config::parse(
+            "default = \"work\"\n[profiles.work]\napi_key = \"abc\"\n[profiles.home]\napi_key = \"def\"\nbase_url = \"https://example.com\"\n",
+        )
+        .expect("the example config should parse");
+        assert_eq!(config.default.as_deref(), Some("work"));
+        assert_eq!(config.profiles.len(), 2);
+        assert_eq!(
+            config
+                .profiles
+                .get("home")
+                .and_then(|p| p.base_url.as_deref()),
+            Some("https://example.com")
+        );
+        assert!(Config::parse("profiles = 3").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! `nuclino edit`: edit an item's content in `$EDITOR`, without clobbering anyone else's
+//! changes made in the meantime.
+
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nuclino_rs::{
+    merge3, Client, Item, ModifyItem, NuclinoError, NuclinoResult, Page, Precondition, Uuid,
+};
+
+use crate::{CliError, CliResult};
+
+/// Where the edit was pushed, or why it wasn't.
+#[derive(Debug)]
+pub enum Outcome {
+    Unchanged,
+    Saved(Box<Page>),
+    Abandoned(PathBuf),
+}
+
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Run the editor on a file, through the shell so editor settings like `code --wait` work.
+fn run_editor(path: &Path) -> CliResult<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor()))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(CliError::Editor(status));
+    }
+    Ok(())
+}
+
+fn ask(question: &str) -> CliResult<char> {
+    eprint!("{question} ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(answer(&line))
+}
+
+/// The choice in a line the user typed: its first letter, lowercased, or a space if
+/// there isn't one.
+fn answer(line: &str) -> char {
+    line.trim()
+        .chars()
+        .next()
+        .unwrap_or(' ')
+        .to_ascii_lowercase()
+}
+
+/// What to do after finding the page was changed on Nuclino during the edit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution {
+    /// Save this text.
+    Save(String),
+    /// Both sides changed the same lines; this text marks them for the user to resolve.
+    Resolve { text: String, conflicts: usize },
+    /// Save nothing.
+    Abort,
+}
+
+/// Act on the user's `choice` of how to handle a page changed on Nuclino: `m` merges
+/// `edited` and `theirs` from `base`, `o` keeps `edited` as is, and anything else aborts.
+fn resolve(base: &str, edited: &str, theirs: &str, choice: char) -> Resolution {
+    match choice {
+        'm' => {
+            let merged = merge3(base, edited, theirs, "your edits", "nuclino");
+            if merged.is_clean() {
+                Resolution::Save(merged.text)
+            } else {
+                Resolution::Resolve {
+                    text: merged.text,
+                    conflicts: merged.conflicts,
+                }
+            }
+        }
+        'o' => Resolution::Save(edited.to_string()),
+        _ => Resolution::Abort,
+    }
+}
+
+/// Whether text still has the markers `merge3()` puts around a conflict.
+fn has_conflict_markers(text: &str) -> bool {
+    text.lines().any(|line| line.starts_with("<<<<<<< "))
+}
+
+fn as_item(page: Page) -> NuclinoResult<Item> {
+    match page {
+        Page::Item(item) => Ok(item),
+        Page::Collection(c) => Err(NuclinoError::NotAnItem(*c.id())),
+    }
+}
+
+/// Make a new directory only the current user can use, to hold the file being edited.
+/// Creating it fresh means nobody else can have planted a link where we'll write.
+fn private_dir() -> CliResult<PathBuf> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    for attempt in 0..100 {
+        let dir = std::env::temp_dir().join(format!(
+            "nuclino-edit-{}-{nanos}-{attempt}",
+            std::process::id()
+        ));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        "could not create a private temporary directory",
+    )
+    .into())
+}
+
+/// Fetch an item, let the user edit it, and push the result if nobody else changed the
+/// page meanwhile. If someone did, offer to merge their changes with ours. The edited
+/// file is removed unless the edit is abandoned, when it's kept for the user.
+pub fn edit(client: &Client, id: &Uuid) -> CliResult<Outcome> {
+    let original = as_item(client.page(id)?)?;
+    let dir = private_dir()?;
+    let path = dir.join(format!("nuclino-{id}.md"));
+    let outcome = edit_file(client, id, &original, &path);
+    if !matches!(outcome, Ok(Outcome::Abandoned(_))) {
+        std::fs::remove_dir_all(&dir).ok();
+    }
+    outcome
+}
+
+fn edit_file(client: &Client, id: &Uuid, original: &Item, path: &Path) -> CliResult<Outcome> {
+    let base = original.content().cloned().unwrap_or_default();
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .write_all(base.as_bytes())?;
+
+    run_editor(path)?;
+    let mut edited = std::fs::read_to_string(path)?;
+    if edited == base {
+        return Ok(Outcome::Unchanged);
+    }
+
+    let current = as_item(client.page(id)?)?;
+    if current.modified() != original.modified() {
+        eprintln!(
+            "'{}' was changed on Nuclino while you were editing it.",
+            current.title()
+        );
+        let theirs = current.content().cloned().unwrap_or_default();
+        let choice = ask("[m]erge their changes with yours, [o]verwrite theirs, or [a]bort?")?;
+        match resolve(&base, &edited, &theirs, choice) {
+            Resolution::Save(text) => edited = text,
+            Resolution::Resolve { text, conflicts } => {
+                std::fs::write(path, &text)?;
+                eprintln!(
+                    "{conflicts} conflicting region(s) are marked in the file. Resolve them, save, and quit."
+                );
+                run_editor(path)?;
+                edited = std::fs::read_to_string(path)?;
+                if has_conflict_markers(&edited)
+                    && ask("Conflict markers remain. Save anyway? [y/n]")? != 'y'
+                {
+                    return Ok(Outcome::Abandoned(path.to_path_buf()));
+                }
+            }
+            Resolution::Abort => {
+                std::fs::write(path, &edited)?;
+                return Ok(Outcome::Abandoned(path.to_path_buf()));
+            }
+        }
+    }
+
+    // Only overwrite the version we merged with (or chose to overwrite), in case the
+    // page changed yet again while we were busy.
+    let expected = Precondition::ModifiedAt(current.modified().to_string());
+    let changes = ModifyItem {
+        title: None,
+        content: Some(edited.clone()),
+    };
+    match client.page_update_if_unmodified(id, &expected, &changes) {
+        Ok(saved) => Ok(Outcome::Saved(Box::new(saved))),
+        Err(NuclinoError::Conflict(_)) => {
+            eprintln!("The page changed again before your edits could be saved.");
+            std::fs::write(path, &edited)?;
+            Ok(Outcome::Abandoned(path.to_path_buf()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        assert_eq!(answer("m\n"), 'm');
+        assert_eq!(answer("  Merge please\n"), 'm');
+        assert_eq!(answer("\n"), ' ');
+        assert_eq!(answer(""), ' ');
+    }
+
+    #[test]
+    fn resolutions() {
+        let base = "one\ntwo\nthree\n";
+        let ours = "ONE\ntwo\nthree\n";
+        let theirs = "one\ntwo\nTHREE\n";
+        assert_eq!(
+            resolve(base, ours, theirs, 'm'),
+            Resolution::Save("ONE\ntwo\nTHREE\n".to_string())
+        );
+        assert_eq!(
+            resolve(base, ours, theirs, 'o'),
+            Resolution::Save(ours.to_string())
+        );
+        assert_eq!(resolve(base, ours, theirs, 'a'), Resolution::Abort);
+        assert_eq!(resolve(base, ours, theirs, ' '), Resolution::Abort);
+
+        let clashing = "uno\ntwo\nthree\n";
+        let Resolution::Resolve { text, conflicts } = resolve(base, ours, clashing, 'm') else {
+            panic!("changing the same line on both sides should conflict");
+        };
+        assert_eq!(conflicts, 1);
+        assert!(has_conflict_markers(&text));
+        assert!(text.contains("<<<<<<< your edits\nONE\n=======\nuno\n>>>>>>> nuclino\n"));
+        assert!(!has_conflict_markers("ONE\ntwo\n"));
+    }
+
+    #[test]
+    fn private_dirs() {
+        let first = private_dir().expect("making a directory should work");
+        let second = private_dir().expect("making another should work");
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&first)
+                .expect("the directory should exist")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        std::fs::remove_dir(&first).ok();
+        std::fs::remove_dir(&second).ok();
+    }
+}
@@ -0,0 +1,393 @@
+//! `nuclino`: a command-line client for the Nuclino wiki, built on `nuclino_rs::Client`.
+//! Credentials come from `NUCLINO_API_KEY` or from a profile in the config file; see
+//! `config.rs` for details.
+
+#![deny(future_incompatible, clippy::unwrap_used)]
+#![warn(rust_2018_idioms, trivial_casts)]
+
+mod config;
+mod edit;
+mod output;
+
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use nuclino_rs::{
+    parse_url, Client, ModifyItem, NewPageBuilder, NuclinoError, NuclinoResult, Page, PageFilter,
+    Uuid,
+};
+use output::{emit, Format, Table};
+use thiserror::Error;
+
+/// Errors from the command-line tool itself, alongside the library's.
+#[derive(Error, Debug)]
+pub enum CliError {
+    /// An error from the Nuclino client.
+    #[error(transparent)]
+    Nuclino(#[from] NuclinoError),
+    /// An IO error.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// An error serializing output as json.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// A configuration file couldn't be used.
+    #[error("Configuration problem: {0}")]
+    Config(String),
+    /// The editor exited unsuccessfully. The file it was editing has been removed.
+    #[error("The editor exited with {0}; your edits were discarded.")]
+    Editor(std::process::ExitStatus),
+}
+
+/// The result type for the command-line tool's functions.
+pub type CliResult<T> = Result<T, CliError>;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "nuclino",
+    version,
+    about = "A command-line client for the Nuclino wiki."
+)]
+struct Cli {
+    /// How to format output.
+    #[arg(long, short, global = true, value_enum, default_value = "table")]
+    format: Format,
+    /// Use the named profile from the config file instead of NUCLINO_API_KEY.
+    #[arg(long, global = true, env = "NUCLINO_PROFILE")]
+    profile: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List the teams you belong to.
+    Teams,
+    /// List the workspaces you can see.
+    Workspaces,
+    /// Show a page, by id or Nuclino url. Items' content is printed after the table.
+    Get {
+        /// The page id or url.
+        page: String,
+    },
+    /// Edit an item's content in $VISUAL or $EDITOR, by id, url, or title path.
+    Edit {
+        /// The page id, url, or title path like `Runbooks/Deploy`.
+        page: String,
+        /// The workspace to look up title paths in.
+        #[arg(long)]
+        workspace: Option<Uuid>,
+    },
+    /// Create an item or collection.
+    Create(CreateArgs),
+    /// Change a page's title or content.
+    Update(UpdateArgs),
+    /// Move a page to the trash.
+    Delete {
+        /// The page id or url.
+        page: String,
+    },
+    /// Search a workspace, a team, or everywhere.
+    Search(SearchArgs),
+    /// List every page in a workspace, without content.
+    List {
+        /// The workspace to list.
+        #[arg(long)]
+        workspace: Uuid,
+        /// Only list pages matching this filter, like `title ~ runbook AND modified < 2026`.
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Show a file's metadata.
+    File {
+        /// The file id.
+        id: Uuid,
+    },
+    /// Download a file.
+    Download {
+        /// The file id.
+        id: Uuid,
+        /// Where to write the file. Defaults to its own name in the current directory.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Where page content comes from.
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+struct ContentArgs {
+    /// Markdown content, given inline.
+    #[arg(long)]
+    content: Option<String>,
+    /// Read Markdown content from this file; `-` reads from stdin.
+    #[arg(long)]
+    content_file: Option<PathBuf>,
+}
+
+impl ContentArgs {
+    fn read(&self) -> NuclinoResult<Option<String>> {
+        if let Some(content) = self.content.as_ref() {
+            return Ok(Some(content.clone()));
+        }
+        match self.content_file.as_ref() {
+            Some(path) if path.as_os_str() == "-" => {
+                let mut content = String::new();
+                std::io::stdin().read_to_string(&mut content)?;
+                Ok(Some(content))
+            }
+            Some(path) => Ok(Some(std::fs::read_to_string(path)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("location").required(true).args(["workspace", "parent"])))]
+struct CreateArgs {
+    /// The new page's title.
+    #[arg(long)]
+    title: String,
+    /// Create the page at the top level of this workspace.
+    #[arg(long)]
+    workspace: Option<Uuid>,
+    /// Create the page inside this collection.
+    #[arg(long)]
+    parent: Option<String>,
+    /// Create a collection instead of an item.
+    #[arg(long)]
+    collection: bool,
+    /// Where to put the page among its siblings.
+    #[arg(long)]
+    index: Option<usize>,
+    #[command(flatten)]
+    content: ContentArgs,
+}
+
+#[derive(Debug, Args)]
+struct UpdateArgs {
+    /// The page id or url.
+    page: String,
+    /// A new title.
+    #[arg(long)]
+    title: Option<String>,
+    #[command(flatten)]
+    content: ContentArgs,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("scope").required(true).args(["workspace", "team", "everywhere"])))]
+struct SearchArgs {
+    /// The text to search for.
+    text: String,
+    /// Search this workspace.
+    #[arg(long)]
+    workspace: Option<Uuid>,
+    /// Search this team.
+    #[arg(long)]
+    team: Option<Uuid>,
+    /// Search every team and workspace you can see, returning every result.
+    #[arg(long)]
+    everywhere: bool,
+    /// Return at most this many results. The server's default is 100.
+    #[arg(long, conflicts_with = "everywhere")]
+    limit: Option<u8>,
+    /// Only show results matching this filter, like `field("Status") != Deprecated`.
+    /// Applied after searching, so it can leave fewer than `--limit` results.
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+/// Accept either a bare page id or a Nuclino url for one.
+fn page_id(input: &str) -> NuclinoResult<Uuid> {
+    if let Ok(id) = Uuid::try_parse(input.trim()) {
+        return Ok(id);
+    }
+    parse_url(input)?
+        .page_id()
+        .copied()
+        .ok_or_else(|| NuclinoError::InvalidUrl(input.to_string()))
+}
+
+/// Parse an optional `--filter`, so a bad one fails before any requests are made.
+fn page_filter(filter: Option<&str>) -> NuclinoResult<Option<PageFilter>> {
+    filter.map(PageFilter::parse).transpose()
+}
+
+fn page_details(page: &Page) -> Table {
+    let mut table = Table::new(&["field", "value"]);
+    table
+        .row(vec!["id".into(), page.id().to_string()])
+        .row(vec!["kind".into(), output::kind(page).into()])
+        .row(vec!["title".into(), page.title().into()])
+        .row(vec!["url".into(), page.url().into()])
+        .row(vec!["workspace".into(), page.workspace().to_string()])
+        .row(vec!["created".into(), page.created().into()])
+        .row(vec!["created by".into(), page.created_by().to_string()])
+        .row(vec!["modified".into(), page.modified().into()])
+        .row(vec!["modified by".into(), page.modified_by().to_string()]);
+    match page {
+        Page::Item(item) => {
+            let mut fields: Vec<(&String, &String)> = item.field_values().iter().collect();
+            fields.sort();
+            for (name, value) in fields {
+                table.row(vec![format!("field: {name}"), value.clone()]);
+            }
+        }
+        Page::Collection(collection) => {
+            table.row(vec![
+                "children".into(),
+                collection.children().len().to_string(),
+            ]);
+        }
+    }
+    table
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let client: Client = config::client(cli.profile.as_deref())?;
+    let format = cli.format;
+    match cli.command {
+        Command::Teams => {
+            let teams = client.all_teams()?;
+            emit(format, &teams, &output::teams_table(&teams))
+        }
+        Command::Workspaces => {
+            let spaces = client.all_workspaces()?;
+            emit(format, &spaces, &output::workspaces_table(&spaces))
+        }
+        Command::Get { page } => {
+            let page = client.page(&page_id(&page)?)?;
+            emit(format, &page, &page_details(&page))?;
+            if let (Format::Table, Page::Item(item)) = (format, &page) {
+                if let Some(content) = item.content() {
+                    println!("\n{content}");
+                }
+            }
+            Ok(())
+        }
+        Command::Edit { page, workspace } => {
+            let id = match (page_id(&page), workspace) {
+                (Ok(id), _) => id,
+                (Err(_), Some(workspace)) => *client.page_by_path(&workspace, &page)?.id(),
+                (Err(e), None) => return Err(e.into()),
+            };
+            match edit::edit(&client, &id)? {
+                edit::Outcome::Unchanged => eprintln!("No changes."),
+                edit::Outcome::Saved(saved) => emit(
+                    format,
+                    &saved,
+                    &output::pages_table(std::slice::from_ref(&saved)),
+                )?,
+                edit::Outcome::Abandoned(path) => {
+                    eprintln!("Nothing was saved. Your edits are in {}", path.display())
+                }
+            }
+            Ok(())
+        }
+        Command::Create(args) => {
+            let mut builder = if args.collection {
+                NewPageBuilder::collection()
+            } else {
+                NewPageBuilder::item()
+            };
+            builder.title(&args.title);
+            if let Some(content) = args.content.read()? {
+                builder.content(&content);
+            }
+            if let Some(index) = args.index {
+                builder.index(index);
+            }
+            if let Some(workspace) = args.workspace.as_ref() {
+                builder.workspace(workspace);
+            }
+            if let Some(parent) = args.parent.as_deref() {
+                builder.parent(&page_id(parent)?);
+            }
+            let created = client.page_create(builder.try_build().map_err(NuclinoError::from)?)?;
+            emit(
+                format,
+                &created,
+                &output::pages_table(std::slice::from_ref(&created)),
+            )
+        }
+        Command::Update(args) => {
+            let changes = ModifyItem {
+                title: args.title.clone(),
+                content: args.content.read()?,
+            };
+            let updated = client.page_update(&page_id(&args.page)?, &changes)?;
+            emit(
+                format,
+                &updated,
+                &output::pages_table(std::slice::from_ref(&updated)),
+            )
+        }
+        Command::Delete { page } => {
+            let deleted = client.page_delete(&page_id(&page)?)?;
+            let mut table = Table::new(&["deleted"]);
+            table.row(vec![deleted.id().to_string()]);
+            emit(format, &deleted, &table)
+        }
+        Command::Search(args) if args.everywhere => {
+            let filter = page_filter(args.filter.as_deref())?;
+            let mut results = client.search_everywhere(&args.text)?;
+            if let Some(filter) = filter {
+                results.retain(|result| filter.matches(&result.page));
+            }
+            emit(format, &results, &output::search_everywhere_table(&results))
+        }
+        Command::Search(args) => {
+            let filter = page_filter(args.filter.as_deref())?;
+            let mut results = match (args.workspace.as_ref(), args.team.as_ref()) {
+                (Some(workspace), _) => {
+                    client.search_workspace(workspace, &args.text, args.limit)?
+                }
+                (None, Some(team)) => client.search_team(team, &args.text, args.limit)?,
+                (None, None) => return Err(NuclinoError::ProgrammerError.into()),
+            };
+            if let Some(filter) = filter {
+                results.retain(|page| filter.matches(page));
+            }
+            emit(format, &results, &output::search_table(&results))
+        }
+        Command::List { workspace, filter } => {
+            let filter = page_filter(filter.as_deref())?;
+            let mut pages = client.workspace_pages(&workspace)?;
+            if let Some(filter) = filter {
+                pages.retain(|page| filter.matches(page));
+            }
+            emit(format, &pages, &output::pages_table(&pages))
+        }
+        Command::File { id } => {
+            let file = client.file(&id)?;
+            emit(
+                format,
+                &file,
+                &output::files_table(std::slice::from_ref(&file)),
+            )
+        }
+        Command::Download { id, output } => {
+            let file = client.file(&id)?;
+            let bytes = client.download_file(file.download_info().url.as_str())?;
+            let path =
+                output.unwrap_or_else(|| PathBuf::from(file.filename().replace(['/', '\\'], "_")));
+            std::fs::write(&path, &bytes)?;
+            eprintln!("wrote {} bytes to {}", bytes.len(), path.display());
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("nuclino: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Printing results as an aligned table, json, or csv.
+
+use clap::ValueEnum;
+use nuclino_rs::{File, Page, SearchResult, Team, Workspace};
+use serde::Serialize;
+
+use crate::CliResult;
+
+/// The output formats we support.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Aligned columns for people.
+    #[default]
+    Table,
+    /// The API's own json, pretty-printed.
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+/// Rows of strings under a header, for the table and csv formats.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Table {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) -> &mut Self {
+        self.rows.push(row);
+        self
+    }
+
+    pub fn render_table(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if let Some(width) = widths.get_mut(i) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+        }
+        let line = |cells: Vec<&str>| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("{}\n", padded.join("  ").trim_end())
+        };
+        let mut out = line(self.headers.clone());
+        for row in &self.rows {
+            out.push_str(&line(row.iter().map(String::as_str).collect()));
+        }
+        out
+    }
+
+    pub fn render_csv(&self) -> String {
+        let mut out = csv_line(self.headers.iter().copied());
+        for row in &self.rows {
+            out.push_str(&csv_line(row.iter().map(String::as_str)));
+        }
+        out
+    }
+}
+
+fn csv_line<'a>(cells: impl Iterator<Item = &'a str>) -> String {
+    let quoted: Vec<String> = cells
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_string()
+            }
+        })
+        .collect();
+    format!("{}\r\n", quoted.join(","))
+}
+
+/// Print a list of things in the chosen format. `table` is used for the table and csv
+/// formats; json output serializes the things themselves.
+pub fn emit<T: Serialize>(format: Format, things: &T, table: &Table) -> CliResult<()> {
+    match format {
+        Format::Table => print!("{}", table.render_table()),
+        Format::Csv => print!("{}", table.render_csv()),
+        Format::Json => println!("{}", serde_json::to_string_pretty(things)?),
+    }
+    Ok(())
+}
+
+pub fn kind(page: &Page) -> &'static str {
+    match page {
+        Page::Item(_) => "item",
+        Page::Collection(_) => "collection",
+    }
+}
+
+pub fn pages_table(pages: &[Page]) -> Table {
+    let mut table = Table::new(&["id", "kind", "title", "modified", "url"]);
+    for page in pages {
+        table.row(vec![
+            page.id().to_string(),
+            kind(page).to_string(),
+            page.title().to_string(),
+            page.modified().to_string(),
+            page.url().to_string(),
+        ]);
+    }
+    table
+}
+
+/// Search results, with the matching text. Matches are marked with `*`, since table
+/// and csv output have no bold.
+pub fn search_table(pages: &[Page]) -> Table {
+    let mut table = Table::new(&["id", "kind", "title", "match", "url"]);
+    for page in pages {
+        table.row(vec![
+            page.id().to_string(),
+            kind(page).to_string(),
+            page.title().to_string(),
+            highlight(page),
+            page.url().to_string(),
+        ]);
+    }
+    table
+}
+
+/// Search results from every workspace, labeled with where they were found.
+pub fn search_everywhere_table(results: &[SearchResult]) -> Table {
+    let mut table = Table::new(&["id", "title", "workspace", "team", "match", "url"]);
+    for result in results {
+        table.row(vec![
+            result.page.id().to_string(),
+            result.page.title().to_string(),
+            result.workspace.clone(),
+            result.team.clone(),
+            highlight(&result.page),
+            result.page.url().to_string(),
+        ]);
+    }
+    table
+}
+
+fn highlight(page: &Page) -> String {
+    match page {
+        Page::Item(item) => item
+            .highlight()
+            .map(|h| h.render("*", "*"))
+            .unwrap_or_default(),
+        Page::Collection(_) => String::new(),
+    }
+}
+
+pub fn teams_table(teams: &[Team]) -> Table {
+    let mut table = Table::new(&["id", "name", "url"]);
+    for team in teams {
+        table.row(vec![
+            team.id().to_string(),
+            team.name().to_string(),
+            team.url().to_string(),
+        ]);
+    }
+    table
+}
+
+pub fn workspaces_table(workspaces: &[Workspace]) -> Table {
+    let mut table = Table::new(&["id", "name", "team", "children"]);
+    for space in workspaces {
+        table.row(vec![
+            space.id().to_string(),
+            space.name().to_string(),
+            space.team_id().to_string(),
+            space.children().len().to_string(),
+        ]);
+    }
+    table
+}
+
+pub fn files_table(files: &[File]) -> Table {
+    let mut table = Table::new(&["id", "filename", "item", "created"]);
+    for file in files {
+        table.row(vec![
+            file.id().to_string(),
+            file.filename().to_string(),
+            file.item_id().to_string(),
+            file.created().to_string(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering() {
+        let mut table = Table::new(&["id", "title"]);
+        table
+            .row(vec!["1".into(), "Deploy, then verify".into()])
+            .row(vec!["22".into(), "A \"quoted\" title".into()]);
+        assert_eq!(
+            table.render_table(),
+            "id  title\n1   Deploy, then verify\n22  A \"quoted\" title\n"
+        );
+        assert_eq!(
+            table.render_csv(),
+            "id,title\r\n1,\"Deploy, then verify\"\r\n22,\"A \"\"quoted\"\" title\"\r\n"
+        );
+    }
+}
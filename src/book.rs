@@ -0,0 +1,321 @@
+//! Exporting a workspace, or one collection in it, as the source of an
+//! [mdBook](https://rust-lang.github.io/mdBook/). Pages are laid out as in a Markdown
+//! export, with collections as directories whose `README.md` lists their children.
+//! `SUMMARY.md` follows the order of the collections' `child_ids`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::export::{
+    item_body, link_path, link_text, relative_path, relink, sanitize_filename, unique_name, Layout,
+};
+use crate::{
+    Attachments, Client, ExportedPage, NuclinoError, NuclinoResult, Page, PageKind, PageTree,
+};
+
+/// What mdBook calls the directory holding a book's Markdown.
+pub static BOOK_SOURCE_DIR: &str = "src";
+
+/// Where attachments go, inside the source directory.
+pub static BOOK_ASSETS_DIR: &str = "assets";
+
+/// Write an mdBook source tree for a hydrated page tree to `dir`: `book.toml`, and
+/// `src/` with `SUMMARY.md`, a chapter per page, and attachments in `src/assets`.
+/// With `collection`, only that collection's descendants are written, and its title
+/// becomes the book's; otherwise the book is the whole workspace. `attachments` holds
+/// the downloaded files for each item, keyed by item id. Returns a record of every
+/// chapter written, with paths relative to `dir`.
+///
+/// Each chapter opens with its page's title as a heading, unless the content already
+/// opens with a `# Heading`. Top-level pages titled like `SUMMARY` or `assets` get
+/// numbered names so they can't overwrite the book's own files.
+pub fn write_book(
+    tree: &PageTree,
+    collection: Option<&Uuid>,
+    attachments: &Attachments,
+    dir: &Path,
+) -> NuclinoResult<Vec<ExportedPage>> {
+    // Keep the book's own files from colliding with pages at the top of the book.
+    let layout = Layout::plan_reserving(
+        tree,
+        "md",
+        "README",
+        collection,
+        &["SUMMARY", BOOK_ASSETS_DIR],
+        None,
+    );
+    let (title, roots, base) = match collection {
+        Some(id) => {
+            let node = tree
+                .get(id)
+                .filter(|node| matches!(node.page(), Page::Collection(_)))
+                .ok_or_else(|| NuclinoError::NotFound(format!("collection {id}")))?;
+            let base = layout.dir(id).cloned().unwrap_or_default();
+            (node.page().title(), node.children(), base)
+        }
+        None => (tree.workspace().name(), tree.roots(), PathBuf::new()),
+    };
+
+    // Chapter paths inside src/, for the pages in the book.
+    let mut chapters: HashMap<Uuid, PathBuf> = HashMap::new();
+    let mut stack: Vec<Uuid> = roots.to_vec();
+    while let Some(id) = stack.pop() {
+        let Some(node) = tree.get(&id) else {
+            continue;
+        };
+        if let Some(path) = layout.file(&id).and_then(|p| p.strip_prefix(&base).ok()) {
+            chapters.insert(id, path.to_path_buf());
+        }
+        stack.extend(node.children());
+    }
+
+    let source = dir.join(BOOK_SOURCE_DIR);
+    std::fs::create_dir_all(source.join(BOOK_ASSETS_DIR))?;
+    std::fs::write(dir.join("book.toml"), book_toml(title))?;
+
+    // Attachments are shared by the whole book, so their names are unique across it.
+    let mut assets: HashMap<Uuid, PathBuf> = HashMap::new();
+    let mut taken = HashSet::new();
+    for node in tree
+        .depth_first()
+        .filter(|node| chapters.contains_key(node.id()))
+    {
+        for (info, bytes) in attachments.get(node.id()).into_iter().flatten() {
+            let name = unique_name(&sanitize_filename(info.filename()), &mut taken);
+            let path = Path::new(BOOK_ASSETS_DIR).join(name);
+            std::fs::write(source.join(&path), bytes)?;
+            assets.insert(*info.id(), path);
+        }
+    }
+
+    let mut summary = String::from("# Summary\n\n");
+    let mut written = Vec::with_capacity(chapters.len());
+    let mut stack: Vec<(Uuid, usize)> = roots.iter().rev().map(|id| (*id, 0)).collect();
+    while let Some((id, depth)) = stack.pop() {
+        let (Some(node), Some(path)) = (tree.get(&id), chapters.get(&id)) else {
+            continue;
+        };
+        let page = node.page();
+        summary.push_str(&format!(
+            "{}- [{}]({})\n",
+            "    ".repeat(depth),
+            link_text(page.title()),
+            chapter_link(path)
+        ));
+        stack.extend(
+            node.children()
+                .iter()
+                .rev()
+                .map(|child| (*child, depth + 1)),
+        );
+
+        let here = path.parent().unwrap_or(Path::new(""));
+        let mut chapter = format!("# {}\n\n", page.title());
+        let kind = match page {
+            Page::Item(item) => {
+                let content = relink(
+                    item.content().map(String::as_str).unwrap_or_default(),
+                    tree,
+                    |id| chapters.get(id).or_else(|| assets.get(id)),
+                    here,
+                );
+                if starts_with_heading(&content) {
+                    chapter.clear();
+                }
+                chapter.push_str(&item_body(Some(&content)));
+                PageKind::Item
+            }
+            Page::Collection(_) => {
+                for child in node.children().iter().filter_map(|id| tree.get(id)) {
+                    if let Some(child_path) = chapters.get(child.id()) {
+                        chapter.push_str(&format!(
+                            "- [{}]({})\n",
+                            link_text(child.page().title()),
+                            chapter_link(&relative_path(here, child_path))
+                        ));
+                    }
+                }
+                PageKind::Collection
+            }
+        };
+        let full = source.join(path);
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&full, chapter)?;
+        written.push(ExportedPage {
+            id,
+            kind,
+            path: Path::new(BOOK_SOURCE_DIR).join(path),
+            modified: page.modified().to_string(),
+        });
+    }
+    std::fs::write(source.join("SUMMARY.md"), summary)?;
+    Ok(written)
+}
+
+/// mdBook reads chapter paths literally, so paths with spaces go in angle brackets
+/// rather than being percent-escaped.
+fn chapter_link(path: &Path) -> String {
+    format!("<{}>", link_path(path))
+}
+
+/// Whether content opens with a `# Heading` of its own, which mdBook will show as the
+/// chapter's title.
+fn starts_with_heading(content: &str) -> bool {
+    let first_line = content.trim_start().lines().next().unwrap_or_default();
+    first_line.starts_with("# ")
+}
+
+fn book_toml(title: &str) -> String {
+    let title = title.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[book]\ntitle = \"{title}\"\nlanguage = \"en\"\nsrc = \"{BOOK_SOURCE_DIR}\"\n")
+}
+
+impl Client {
+    /// Export a workspace, or one collection in it, as an mdBook source tree. Every item
+    /// is fetched for its content and every attachment is downloaded, so this makes one
+    /// request per item and two per attachment.
+    pub fn export_book(
+        &self,
+        workspace: &Uuid,
+        collection: Option<&Uuid>,
+        dir: &Path,
+    ) -> NuclinoResult<Vec<ExportedPage>> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        let in_book = |mut id: Uuid| match collection {
+            None => true,
+            Some(collection) => loop {
+                match tree.parent_of(&id) {
+                    Some(parent) if parent.id() == collection => break true,
+                    Some(parent) => id = *parent.id(),
+                    None => break false,
+                }
+            },
+        };
+        let attachments = self.tree_attachments(&tree, |id| in_book(*id))?;
+        write_book(&tree, collection, &attachments, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        let deploy = format!(
+            "See [rollback](https://app.nuclino.com/t/b/{rollback}) and <https://app.nuclino.com/t/b/{welcome}>.\n\n\
+             ![diagram](https://files.nuclino.com/files/{file}/diagram.png)\n",
+            rollback = id(3),
+            welcome = id(4),
+            file = id(10),
+        );
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(3), id(2)]),
+            item(id(2), "Deploy [prod]", Some(&deploy)),
+            item(id(3), "Roll back", Some("Undo it.")),
+            item(id(4), "Welcome", Some("Hello.")),
+        ];
+        PageTree::build(workspace(&[id(4), id(1)]), pages).expect("sample tree should build")
+    }
+
+    fn attachments() -> Attachments {
+        let mut attachments = HashMap::new();
+        attachments.insert(id(2), vec![(file(id(10), id(2), "diagram.png"), vec![7])]);
+        attachments
+    }
+
+    #[test]
+    fn whole_workspace() {
+        let dir = std::env::temp_dir().join(format!("nuclino-book-{}", std::process::id()));
+        let written =
+            write_book(&sample(), None, &attachments(), &dir).expect("export should succeed");
+        assert_eq!(written.len(), 4);
+
+        let summary =
+            std::fs::read_to_string(dir.join("src/SUMMARY.md")).expect("summary should exist");
+        assert_eq!(
+            summary,
+            "# Summary\n\n\
+             - [Welcome](<Welcome.md>)\n\
+             - [Runbooks](<Runbooks/README.md>)\n    \
+             - [Roll back](<Runbooks/Roll back.md>)\n    \
+             - [Deploy \\[prod\\]](<Runbooks/Deploy [prod].md>)\n"
+        );
+        let toml = std::fs::read_to_string(dir.join("book.toml")).expect("book.toml should exist");
+        assert!(toml.contains("title = \"General\""));
+
+        let deploy = std::fs::read_to_string(dir.join("src/Runbooks/Deploy [prod].md"))
+            .expect("chapter should exist");
+        assert!(deploy.starts_with("# Deploy [prod]\n\n"));
+        assert!(deploy.contains("[rollback](Roll%20back.md)"));
+        assert!(deploy.contains("and [Welcome](../Welcome.md)."));
+        assert!(deploy.contains("![diagram](../assets/diagram.png)"));
+        assert_eq!(
+            std::fs::read(dir.join("src/assets/diagram.png")).expect("asset should exist"),
+            vec![7]
+        );
+
+        let index = std::fs::read_to_string(dir.join("src/Runbooks/README.md"))
+            .expect("index should exist");
+        assert!(index.contains(
+            "- [Roll back](<Roll back.md>)\n- [Deploy \\[prod\\]](<Deploy [prod].md>)\n"
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn one_collection() {
+        let dir =
+            std::env::temp_dir().join(format!("nuclino-book-collection-{}", std::process::id()));
+        let written = write_book(&sample(), Some(&id(1)), &attachments(), &dir)
+            .expect("export should succeed");
+        let paths: Vec<&Path> = written.iter().map(|p| p.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("src/Roll back.md"),
+                Path::new("src/Deploy [prod].md")
+            ]
+        );
+        let toml = std::fs::read_to_string(dir.join("book.toml")).expect("book.toml should exist");
+        assert!(toml.contains("title = \"Runbooks\""));
+        // Links to pages outside the book still point at Nuclino.
+        let deploy = std::fs::read_to_string(dir.join("src/Deploy [prod].md"))
+            .expect("chapter should exist");
+        assert!(deploy.contains(&format!("<https://app.nuclino.com/t/b/{}>", id(4))));
+        assert!(deploy.contains("![diagram](assets/diagram.png)"));
+
+        assert!(write_book(&sample(), Some(&id(2)), &attachments(), &dir).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn reserved_names() {
+        let dir =
+            std::env::temp_dir().join(format!("nuclino-book-reserved-{}", std::process::id()));
+        let pages = vec![
+            item(id(1), "SUMMARY", Some("# SUMMARY\n\nNot the real one.")),
+            collection(id(2), "Assets", &[id(3)]),
+            item(id(3), "Logo", Some("A picture.")),
+        ];
+        let tree = PageTree::build(workspace(&[id(1), id(2)]), pages).expect("tree should build");
+        write_book(&tree, None, &HashMap::new(), &dir).expect("export should succeed");
+
+        let summary =
+            std::fs::read_to_string(dir.join("src/SUMMARY.md")).expect("summary should exist");
+        assert!(summary.starts_with("# Summary\n\n- [SUMMARY](<SUMMARY (2).md>)\n"));
+        assert!(summary.contains("- [Assets](<Assets (2)/README.md>)"));
+        let chapter =
+            std::fs::read_to_string(dir.join("src/SUMMARY (2).md")).expect("chapter should exist");
+        assert_eq!(chapter, "# SUMMARY\n\nNot the real one.\n");
+        let logo = std::fs::read_to_string(dir.join("src/Assets (2)/Logo.md"))
+            .expect("chapter should exist");
+        assert_eq!(logo, "# Logo\n\nA picture.\n");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,384 @@
+//! Line-level text diffs.
+
+use std::fmt::Display;
+
+/// One line of a diff between two texts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// A line present in both texts.
+    Same(String),
+    /// A line only in the newer text.
+    Added(String),
+    /// A line only in the older text.
+    Removed(String),
+}
+
+impl DiffLine {
+    /// True if this line is an addition or removal.
+    pub fn is_change(&self) -> bool {
+        !matches!(self, DiffLine::Same(_))
+    }
+}
+
+impl Display for DiffLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffLine::Same(line) => write!(f, "  {line}"),
+            DiffLine::Added(line) => write!(f, "+ {line}"),
+            DiffLine::Removed(line) => write!(f, "- {line}"),
+        }
+    }
+}
+
+/// Diff two texts line by line, using the longest common subsequence of their lines.
+/// This takes time proportional to the texts' length times the number of changed
+/// lines, and space proportional to their length.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    lcs_ops(&old, &new)
+        .into_iter()
+        .map(|(op, line)| match op {
+            Op::Same => DiffLine::Same(line.to_string()),
+            Op::Added => DiffLine::Added(line.to_string()),
+            Op::Removed => DiffLine::Removed(line.to_string()),
+        })
+        .collect()
+}
+
+/// Format a diff as hunks of changes with `context` unchanged lines around each,
+/// eliding the unchanged stretches in between.
+pub fn format_hunks(diff: &[DiffLine], context: usize) -> String {
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.is_change())
+        .map(|(i, _)| i)
+        .collect();
+    let mut out = String::new();
+    let mut last_shown: Option<usize> = None;
+    for (i, line) in diff.iter().enumerate() {
+        let near = changed
+            .iter()
+            .any(|&c| i + context >= c && i <= c + context);
+        if !near {
+            continue;
+        }
+        if let Some(last) = last_shown {
+            if i > last + 1 {
+                out.push_str("  ...\n");
+            }
+        }
+        out.push_str(&format!("{line}\n"));
+        last_shown = Some(i);
+    }
+    out
+}
+
+/// The result of a three-way merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    /// The merged text. Conflicting regions are wrapped in `<<<<<<<`, `=======`, and
+    /// `>>>>>>>` marker lines, like git's.
+    pub text: String,
+    /// How many conflicting regions the merged text contains.
+    pub conflicts: usize,
+}
+
+impl Merge {
+    /// True if both sides' changes merged cleanly.
+    pub fn is_clean(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// Merge two edited versions of a text, given the version they both started from.
+/// Regions only one side changed take that side's change; regions both sides changed
+/// identically are taken once; regions both changed differently become conflicts,
+/// labelled with `ours_label` and `theirs_label`.
+pub fn merge3(base: &str, ours: &str, theirs: &str, ours_label: &str, theirs_label: &str) -> Merge {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+    let ours_at = base_matches(&base_lines, &our_lines);
+    let theirs_at = base_matches(&base_lines, &their_lines);
+
+    let mut merged: Vec<String> = Vec::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // The next base line both sides kept is a point where they agree again.
+        let sync = (i..base_lines.len()).find_map(|b| match (ours_at[b], theirs_at[b]) {
+            (Some(o), Some(t)) if o >= j && t >= k => Some((b, o, t)),
+            _ => None,
+        });
+        let (b_end, o_end, t_end) =
+            sync.unwrap_or((base_lines.len(), our_lines.len(), their_lines.len()));
+        let base_chunk = &base_lines[i..b_end];
+        let our_chunk = &our_lines[j..o_end];
+        let their_chunk = &their_lines[k..t_end];
+
+        if our_chunk == base_chunk {
+            merged.extend(their_chunk.iter().map(|l| l.to_string()));
+        } else if their_chunk == base_chunk || our_chunk == their_chunk {
+            merged.extend(our_chunk.iter().map(|l| l.to_string()));
+        } else {
+            conflicts += 1;
+            merged.push(format!("<<<<<<< {ours_label}"));
+            merged.extend(our_chunk.iter().map(|l| l.to_string()));
+            merged.push("=======".to_string());
+            merged.extend(their_chunk.iter().map(|l| l.to_string()));
+            merged.push(format!(">>>>>>> {theirs_label}"));
+        }
+
+        let Some((b, o, t)) = sync else {
+            break;
+        };
+        merged.push(base_lines[b].to_string());
+        i = b + 1;
+        j = o + 1;
+        k = t + 1;
+    }
+
+    let mut text = merged.join("\n");
+    if !text.is_empty() && (ours.ends_with('\n') || theirs.ends_with('\n')) {
+        text.push('\n');
+    }
+    Merge { text, conflicts }
+}
+
+/// For each line of `base`, the index of the line it matches in `other`, if it survived.
+fn base_matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    let (mut b, mut o) = (0, 0);
+    for (op, _) in lcs_ops(base, other) {
+        match op {
+            Op::Same => {
+                matches[b] = Some(o);
+                b += 1;
+                o += 1;
+            }
+            Op::Removed => b += 1,
+            Op::Added => o += 1,
+        }
+    }
+    matches
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Same,
+    Added,
+    Removed,
+}
+
+/// The shortest edit script turning `old` into `new`, found with Myers' O(ND) algorithm
+/// in its linear-space form. Within each run of changes, removals come before additions.
+pub(crate) fn lcs_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let mut ops: Vec<(Op, &str)> = Vec::with_capacity(old.len().max(new.len()));
+    myers(old, new, &mut ops);
+    // The recursion can interleave additions and removals; group them for readability.
+    let mut start = 0;
+    while start < ops.len() {
+        if ops[start].0 == Op::Same {
+            start += 1;
+            continue;
+        }
+        let end = ops[start..]
+            .iter()
+            .position(|(op, _)| *op == Op::Same)
+            .map_or(ops.len(), |n| start + n);
+        ops[start..end].sort_by_key(|(op, _)| *op == Op::Added);
+        start = end;
+    }
+    ops
+}
+
+/// Append the edit script for `old` to `new` to `ops`, splitting the problem at the
+/// middle snake of an optimal path so only two vectors of diagonals are ever held.
+fn myers<'a>(old: &[&'a str], new: &[&'a str], ops: &mut Vec<(Op, &'a str)>) {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    ops.extend(old[..prefix].iter().map(|l| (Op::Same, *l)));
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    if old_mid.is_empty() {
+        ops.extend(new_mid.iter().map(|l| (Op::Added, *l)));
+    } else if new_mid.is_empty() {
+        ops.extend(old_mid.iter().map(|l| (Op::Removed, *l)));
+    } else {
+        // With the shared ends trimmed, the snake is never at either corner, so both
+        // halves are smaller than the whole.
+        let (x, y, u, v) = middle_snake(old_mid, new_mid);
+        myers(&old_mid[..x], &new_mid[..y], ops);
+        ops.extend(old_mid[x..u].iter().map(|l| (Op::Same, *l)));
+        myers(&old_mid[u..], &new_mid[v..], ops);
+    }
+
+    ops.extend(old[old.len() - suffix..].iter().map(|l| (Op::Same, *l)));
+}
+
+/// The middle snake of a shortest edit path from `old` to `new`, as its start and end
+/// points `(x, y, u, v)`: `old[x..u]` matches `new[y..v]`. Searches forward from the
+/// start and backward from the end at once until the two searches overlap.
+fn middle_snake(old: &[&str], new: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // forward[k] is how far along old the furthest forward path on diagonal k reaches;
+    // backward[k] is the same for paths from the end, measured from the end.
+    let mut forward = vec![0isize; (2 * offset + 1) as usize];
+    let mut backward = vec![0isize; (2 * offset + 1) as usize];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            let back = delta - k;
+            if delta % 2 != 0 && back.abs() < d && x + backward[at(back)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && old[(n - 1 - x) as usize] == new[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            let ahead = delta - k;
+            if delta % 2 == 0 && ahead.abs() <= d && forward[at(ahead)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+    unreachable!("the forward and backward searches always meet by the middle")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs() {
+        let old = "# Title\none\ntwo\nthree\nfour\n";
+        let new = "# Title\none\n2\nthree\nfour\nfive\n";
+        let diff = diff_lines(old, new);
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("# Title".into()),
+                DiffLine::Same("one".into()),
+                DiffLine::Removed("two".into()),
+                DiffLine::Added("2".into()),
+                DiffLine::Same("three".into()),
+                DiffLine::Same("four".into()),
+                DiffLine::Added("five".into()),
+            ]
+        );
+        assert!(diff_lines(old, old).iter().all(|l| !l.is_change()));
+    }
+
+    #[test]
+    fn clean_merges() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let ours = "ONE\ntwo\nthree\nfour\n";
+        let theirs = "one\ntwo\nthree\nFOUR\nfive\n";
+        let merged = merge3(base, ours, theirs, "ours", "theirs");
+        assert!(merged.is_clean());
+        assert_eq!(merged.text, "ONE\ntwo\nthree\nFOUR\nfive\n");
+
+        let same = merge3(base, ours, ours, "ours", "theirs");
+        assert_eq!(same.text, ours);
+    }
+
+    #[test]
+    fn conflicting_merges() {
+        let base = "one\ntwo\nthree\n";
+        let ours = "one\n2\nthree\n";
+        let theirs = "one\ndeux\nthree\n";
+        let merged = merge3(base, ours, theirs, "local", "nuclino");
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.text,
+            "one\n<<<<<<< local\n2\n=======\ndeux\n>>>>>>> nuclino\nthree\n"
+        );
+    }
+
+    #[test]
+    fn merging_long_texts() {
+        // A full LCS table for these would need billions of cells.
+        let base: String = (0..50_000).map(|n| format!("line {n}\n")).collect();
+        let ours = base.replacen("line 100\n", "line one hundred\n", 1);
+        let theirs = base.replacen("line 49000\n", "line forty-nine thousand\n", 1);
+        let merged = merge3(&base, &ours, &theirs, "ours", "theirs");
+        assert!(merged.is_clean());
+        assert_eq!(
+            merged.text,
+            ours.replacen("line 49000\n", "line forty-nine thousand\n", 1)
+        );
+    }
+
+    #[test]
+    fn diffing_edits_far_apart() {
+        let old: String = (0..20_000).map(|n| format!("line {n}\n")).collect();
+        let new = old
+            .replacen("line 3\n", "line three\n", 1)
+            .replacen("line 19990\n", "", 1);
+        let changes: Vec<DiffLine> = diff_lines(&old, &new)
+            .into_iter()
+            .filter(DiffLine::is_change)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                DiffLine::Removed("line 3".into()),
+                DiffLine::Added("line three".into()),
+                DiffLine::Removed("line 19990".into()),
+            ]
+        );
+        let merged = merge3(&old, &new, &old, "ours", "theirs");
+        assert_eq!(merged.text, new);
+    }
+
+    #[test]
+    fn hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\n";
+        let new = "A\nb\nc\nd\ne\nf\nG\n";
+        let formatted = format_hunks(&diff_lines(old, new), 1);
+        assert_eq!(formatted, "- a\n+ A\n  b\n  ...\n  f\n- g\n+ G\n");
+    }
+}
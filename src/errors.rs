@@ -1,16 +1,22 @@
 //! Client errors, with one-hopes-helpful messages.
 
+use std::collections::HashMap;
 use std::num::ParseIntError;
 
 use thiserror::Error;
+use uuid::Uuid;
+
+use crate::Page;
 
 /// A convenient alias for the error type used by all crate functions.
 pub type NuclinoResult<T> = Result<T, NuclinoError>;
 
 /// Errors returned by this crate's functions. These include errors
 /// derived from serde_json and ureq as well as errors representing
-/// failure responses from the Nuclino API.
+/// failure responses from the Nuclino API. New variants may be added in minor
+/// releases, so matches on it need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum NuclinoError {
     /// Api key env var was required, but not found.
     #[error("Cannot find an API key in the process environment.")]
@@ -49,17 +55,119 @@ pub enum NuclinoError {
     /// A successful response from Nuclino did not include a data field in its wrapper.
     #[error("Didn't get a data field on the response")]
     NoDataReturned,
+    /// A string we were asked to parse as a Nuclino url wasn't one.
+    #[error("Not a recognizable Nuclino url: {0}")]
+    InvalidUrl(String),
+    /// Something we looked for by a name or url rather than by id doesn't exist.
+    #[error("Not found: {0}")]
+    NotFound(String),
+    /// Following `child_ids` from a page led back to that page.
+    #[error("Page {0} contains itself, directly or indirectly.")]
+    TreeCycle(Uuid),
+    /// A page is listed as a child of more than one parent. A parent id equal to the
+    /// workspace id means the page is also at the top level of the workspace.
+    #[error("Page {child} is a child of both {first} and {second}.")]
+    DuplicateParent {
+        /// the page with two parents
+        child: Uuid,
+        /// the parent where we found it first
+        first: Uuid,
+        /// the parent where we found it again
+        second: Uuid,
+    },
+    /// A title path matched more than one page.
+    #[error("The path '{path}' matches {} pages.", .matches.len())]
+    AmbiguousPath {
+        /// the path we were looking up
+        path: String,
+        /// ids of every page the path matched, in sidebar order
+        matches: Vec<Uuid>,
+    },
+    /// A conditional update found the page had changed since the caller last saw it.
+    /// Carries the page as it is now.
+    #[error("Page {} was modified at {}, so the update was not made.", .0.id(), .0.modified())]
+    Conflict(Box<Page>),
+    /// An operation that needs Markdown content was given a collection.
+    #[error("Page {0} is a collection, which has no content.")]
+    NotAnItem(Uuid),
+    /// A page filter expression couldn't be parsed.
+    #[error("Invalid filter at position {position}: {message}")]
+    InvalidFilter {
+        /// the byte offset in the expression where the problem was found
+        position: usize,
+        /// what was wrong
+        message: String,
+    },
+    /// A new page failed validation before being sent to Nuclino.
+    #[error(transparent)]
+    InvalidNewPage(#[from] NewPageError),
+    /// Restoring a backup failed after some pages had been created. They are still in
+    /// the workspace, and links between them may not have been rewritten yet.
+    #[error("Restore stopped after creating {} pages: {source}", .restored.len())]
+    PartialRestore {
+        /// each backed-up page id that was restored, mapped to its new copy's id
+        restored: HashMap<Uuid, Uuid>,
+        /// the error that stopped the restore
+        source: Box<NuclinoError>,
+    },
     /// The author of this crate made an error. Please report this as a bug.
     #[error("Programmer error. Please file a bug.")]
     ProgrammerError,
 }
 
+/// The ways a `NewPageBuilder` can be misconfigured. Nuclino would reject any of
+/// these with a 400, so `try_build()` catches them before a request goes out.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum NewPageError {
+    /// Neither a workspace nor a parent collection was given for the new page.
+    #[error("A new page needs either a workspace id or a parent id.")]
+    MissingLocation,
+    /// Markdown content was provided for a collection, which can't have any.
+    #[error("Collections cannot have content.")]
+    ContentOnCollection,
+    /// The title was set, but to an empty or whitespace-only string.
+    #[error("The page title is empty.")]
+    EmptyTitle,
+    /// The title is longer than Nuclino allows.
+    #[error("The page title is {length} characters long; the maximum is {max}.")]
+    TitleTooLong {
+        /// the length of the title provided, in characters
+        length: usize,
+        /// the longest title we allow
+        max: usize,
+    },
+}
+
+impl NuclinoError {
+    /// Whether this is the API saying that what was asked for doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, NuclinoError::ClientError { status: 404, .. })
+    }
+}
+
 impl From<ureq::Error> for NuclinoError {
     fn from(value: ureq::Error) -> Self {
-        NuclinoError::RequestError(value.to_string())
+        match value {
+            // ureq treats 4xx and 5xx responses as errors.
+            ureq::Error::Status(status, response) => status_error(status, response),
+            other => NuclinoError::RequestError(other.to_string()),
+        }
     }
 }
 
+/// Turn an http error response from Nuclino into `ClientError` (4xx) or `ServerError`
+/// (5xx), carrying its status and the message Nuclino sent, or the status text if it
+/// sent none. Before 2.0.0 these were `RequestError`s holding ureq's description.
+fn status_error(status: u16, response: ureq::Response) -> NuclinoError {
+    let fallback = response.status_text().to_string();
+    let message = response
+        .into_json::<serde_json::Value>()
+        .ok()
+        .and_then(|body| body.get("message")?.as_str().map(str::to_string))
+        .unwrap_or(fallback);
+    make_error(status, message)
+}
+
 /// An internal convenience for making Nuclino API responses into errors.
 pub fn make_error(status: u16, message: String) -> NuclinoError {
     if status < 500 {
@@ -68,3 +176,68 @@ pub fn make_error(status: u16, message: String) -> NuclinoError {
         NuclinoError::ServerError { status, message }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn http_errors_keep_their_status() {
+        let (url, server) = serve(vec![
+            not_found(),
+            (
+                503,
+                json!({ "status": "error", "message": "Try again later" }),
+            ),
+        ]);
+        let client = local_client(&url);
+        let missing = client.page(&id(1)).expect_err("a 404 should be an error");
+        assert!(missing.is_not_found(), "{missing:?}");
+        assert!(matches!(
+            missing,
+            NuclinoError::ClientError { status: 404, ref message } if message == "Item not found"
+        ));
+        let down = client.page(&id(2)).expect_err("a 503 should be an error");
+        assert!(matches!(
+            down,
+            NuclinoError::ServerError { status: 503, .. }
+        ));
+        assert!(!down.is_not_found());
+        server.join().expect("the server should finish");
+    }
+
+    #[test]
+    fn failure_bodies_on_success_statuses() {
+        let (url, server) = serve(vec![
+            (200, json!({ "status": "error", "message": "Broken" })),
+            (200, json!({ "status": "fail", "message": "Bad id" })),
+            (200, json!({ "status": "error", "message": "Broken" })),
+        ]);
+        let client = local_client(&url);
+        let fetched = client
+            .page(&id(1))
+            .expect_err("an error body should be an error");
+        assert!(matches!(
+            fetched,
+            NuclinoError::ServerError { status: 200, ref message } if message == "Broken"
+        ));
+        let deleted = client
+            .page_delete(&id(1))
+            .expect_err("a fail body should be an error");
+        assert!(matches!(
+            deleted,
+            NuclinoError::ClientError { status: 200, .. }
+        ));
+        let created = client
+            .page_create(crate::NewPageBuilder::item().workspace(&id(2)).build())
+            .expect_err("an error body should be an error");
+        assert!(matches!(
+            created,
+            NuclinoError::ServerError { status: 200, .. }
+        ));
+        server.join().expect("the server should finish");
+    }
+}
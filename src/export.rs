@@ -0,0 +1,576 @@
+//! Exporting a workspace to a directory of Markdown files with YAML front matter.
+//! Items become `.md` files and collections become directories with an `index.md`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::frontmatter::{FrontMatter, Value};
+use crate::sync::synced_hash;
+use crate::{
+    find_links, Client, File, LinkKind, Manifest, ManifestEntry, NuclinoResult, Page, PageKind,
+    PageTree,
+};
+
+/// The file name, without extension, used for a collection's own page inside its directory.
+pub static INDEX_NAME: &str = "index";
+
+/// Downloaded attachments, as pairs of file metadata and contents, keyed by the id of
+/// the item they belong to.
+pub type Attachments = HashMap<Uuid, Vec<(File, Vec<u8>)>>;
+
+/// A record of one page written by an export.
+#[derive(Debug, Clone)]
+pub struct ExportedPage {
+    /// The id of the exported page.
+    pub id: Uuid,
+    /// Whether the page was an item or a collection.
+    pub kind: PageKind,
+    /// Where the page was written, relative to the export directory. For collections
+    /// this is the index file inside the collection's directory.
+    pub path: PathBuf,
+    /// The page's last-modified time when it was exported.
+    pub modified: String,
+}
+
+/// Where each page in a tree goes on disk, relative to the output directory.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Layout {
+    files: HashMap<Uuid, PathBuf>,
+    dirs: HashMap<Uuid, PathBuf>,
+}
+
+impl Layout {
+    /// Assign every page in the tree a file name derived from its title, unique within
+    /// its directory. Collections also get a directory, and their own file is named
+    /// `index_name` inside it.
+    pub(crate) fn plan(tree: &PageTree, extension: &str, index_name: &str) -> Self {
+        Self::plan_with(
+            tree,
+            extension,
+            index_name,
+            (None, &[]),
+            None,
+            sanitize_filename,
+        )
+    }
+
+    /// Like `plan()`, but keeps each of `reserved` free as a name in the directory of the
+    /// collection `root`, or at the top level without one, for outputs that write files
+    /// of their own there. With `companion`, each item also claims its file name plus that
+    /// suffix in its directory, for outputs that put a directory of attachments beside it.
+    pub(crate) fn plan_reserving(
+        tree: &PageTree,
+        extension: &str,
+        index_name: &str,
+        root: Option<&Uuid>,
+        reserved: &[&str],
+        companion: Option<&str>,
+    ) -> Self {
+        Self::plan_with(
+            tree,
+            extension,
+            index_name,
+            (root, reserved),
+            companion,
+            sanitize_filename,
+        )
+    }
+
+    /// Like `plan()`, but turning titles into file names with `name` instead of
+    /// `sanitize_filename()`.
+    pub(crate) fn plan_named(
+        tree: &PageTree,
+        extension: &str,
+        index_name: &str,
+        name: fn(&str) -> String,
+    ) -> Self {
+        Self::plan_with(tree, extension, index_name, (None, &[]), None, name)
+    }
+
+    fn plan_with(
+        tree: &PageTree,
+        extension: &str,
+        index_name: &str,
+        (root, root_reserved): (Option<&Uuid>, &[&str]),
+        companion: Option<&str>,
+        name: fn(&str) -> String,
+    ) -> Self {
+        let mut layout = Layout::default();
+        let mut taken: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+        for node in tree.depth_first() {
+            let parent_dir = node
+                .parent()
+                .and_then(|p| layout.dirs.get(p))
+                .cloned()
+                .unwrap_or_default();
+            let names = taken.entry(parent_dir.clone()).or_insert_with(|| {
+                let mut reserved = HashSet::new();
+                if !parent_dir.as_os_str().is_empty() {
+                    reserved.insert(index_name.to_lowercase());
+                }
+                if node.parent() == root {
+                    reserved.extend(root_reserved.iter().map(|name| name.to_lowercase()));
+                }
+                reserved
+            });
+            let stem = match (node.page(), companion) {
+                (Page::Item(_), Some(suffix)) => {
+                    unique_with_companion(&name(node.page().title()), suffix, names)
+                }
+                _ => unique_name(&name(node.page().title()), names),
+            };
+            match node.page() {
+                Page::Item(_) => {
+                    layout
+                        .files
+                        .insert(*node.id(), parent_dir.join(format!("{stem}.{extension}")));
+                }
+                Page::Collection(_) => {
+                    let dir = parent_dir.join(&stem);
+                    layout
+                        .files
+                        .insert(*node.id(), dir.join(format!("{index_name}.{extension}")));
+                    layout.dirs.insert(*node.id(), dir);
+                }
+            }
+        }
+        layout
+    }
+
+    /// The file for a page.
+    pub(crate) fn file(&self, id: &Uuid) -> Option<&PathBuf> {
+        self.files.get(id)
+    }
+
+    /// The directory for a collection.
+    pub(crate) fn dir(&self, id: &Uuid) -> Option<&PathBuf> {
+        self.dirs.get(id)
+    }
+}
+
+/// Turn a page title into something safe to use as a file name on any common platform.
+pub(crate) fn sanitize_filename(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_matches(|c: char| c == '.' || c == ' ');
+    let mut name: String = trimmed.chars().take(100).collect();
+    if name.is_empty() {
+        name = "Untitled".to_string();
+    }
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(&name)) {
+        name.push('_');
+    }
+    name
+}
+
+/// Make `name` unique among `taken`, case-insensitively, by appending a counter.
+pub(crate) fn unique_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut candidate = name.to_string();
+    let mut counter = 2;
+    while taken.contains(&candidate.to_lowercase()) {
+        candidate = format!("{name} ({counter})");
+        counter += 1;
+    }
+    taken.insert(candidate.to_lowercase());
+    candidate
+}
+
+/// A page title made safe to use as Markdown link text, with the characters that would
+/// end the link or start emphasis escaped.
+pub(crate) fn link_text(title: &str) -> String {
+    let mut text = String::with_capacity(title.len());
+    for c in title.chars() {
+        if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`') {
+            text.push('\\');
+        }
+        text.push(c);
+    }
+    text
+}
+
+/// Like `unique_name()`, but also keeping `name` plus `suffix` free, and claiming both.
+fn unique_with_companion(name: &str, suffix: &str, taken: &mut HashSet<String>) -> String {
+    let mut candidate = name.to_string();
+    let mut counter = 2;
+    while taken.contains(&candidate.to_lowercase())
+        || taken.contains(&format!("{candidate}{suffix}").to_lowercase())
+    {
+        candidate = format!("{name} ({counter})");
+        counter += 1;
+    }
+    taken.insert(candidate.to_lowercase());
+    taken.insert(format!("{candidate}{suffix}").to_lowercase());
+    candidate
+}
+
+/// The path to `to` relative to the directory `from_dir`. Both must be relative to
+/// the same root.
+pub(crate) fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component<'_>> = from_dir.components().collect();
+    let target: Vec<Component<'_>> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut result = PathBuf::new();
+    for _ in common..from.len() {
+        result.push("..");
+    }
+    for component in &target[common..] {
+        result.push(component);
+    }
+    result
+}
+
+/// Point links to pages and files at their paths relative to `here`, for exports where
+/// every page and file has a local copy. `target` gives the path of each one that does.
+/// Mentions, which are bare urls, become Markdown links titled with the page they
+/// mention.
+pub(crate) fn relink<'a, F>(content: &str, tree: &PageTree, target: F, here: &Path) -> String
+where
+    F: Fn(&Uuid) -> Option<&'a PathBuf>,
+{
+    let mut rewritten = String::with_capacity(content.len());
+    let mut copied = 0;
+    for link in find_links(content) {
+        let Some(path) = target(&link.id) else {
+            continue;
+        };
+        let relative = link_target(&relative_path(here, path));
+        match link.kind {
+            LinkKind::Mention => {
+                let title = tree
+                    .get(&link.id)
+                    .map(|node| node.page().title().to_string())
+                    .unwrap_or_else(|| relative.clone());
+                rewritten.push_str(&content[copied..link.span.start]);
+                rewritten.push_str(&format!("[{}]({relative})", title.replace(['[', ']'], "")));
+                copied = link.span.end;
+            }
+            LinkKind::Item | LinkKind::File => {
+                rewritten.push_str(&content[copied..link.url_span.start]);
+                rewritten.push_str(&relative);
+                copied = link.url_span.end;
+            }
+        }
+    }
+    rewritten.push_str(&content[copied..]);
+    rewritten
+}
+
+/// A relative path formatted for use in a Markdown link: forward slashes, with
+/// characters that would end the link or be read as part of a URL escaped.
+pub(crate) fn link_target(path: &Path) -> String {
+    let mut target = String::new();
+    for c in link_path(path).chars() {
+        match c {
+            '%' | ' ' | '(' | ')' | '#' | '?' | '[' | ']' | '<' | '>' | '"' | '\\' | '^' | '`'
+            | '{' | '|' | '}' => target.push_str(&format!("%{:02X}", c as u32)),
+            c => target.push(c),
+        }
+    }
+    target
+}
+
+/// A path relative to the export root as a string with `/` separators, which is how
+/// manifests record them.
+pub(crate) fn link_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The Markdown we write below an item's front matter: its content, ending in a newline.
+pub(crate) fn item_body(content: Option<&str>) -> String {
+    let mut body = content.unwrap_or_default().to_string();
+    if !body.is_empty() && !body.ends_with('\n') {
+        body.push('\n');
+    }
+    body
+}
+
+/// An item's complete exported file: front matter, a blank line, then the content.
+pub(crate) fn item_document(page: &Page) -> String {
+    let content = match page {
+        Page::Item(item) => item.content().map(String::as_str),
+        Page::Collection(_) => None,
+    };
+    format!(
+        "{}\n{}",
+        page_front_matter(page).render(),
+        item_body(content)
+    )
+}
+
+/// Build the front matter we write for a page.
+pub(crate) fn page_front_matter(page: &Page) -> FrontMatter {
+    let mut matter = FrontMatter::new();
+    matter
+        .string("id", &page.id().to_string())
+        .string(
+            "object",
+            match page {
+                Page::Item(_) => "item",
+                Page::Collection(_) => "collection",
+            },
+        )
+        .string("title", page.title())
+        .string("url", page.url())
+        .string("created", page.created())
+        .string("created_by", &page.created_by().to_string())
+        .string("modified", page.modified())
+        .string("modified_by", &page.modified_by().to_string());
+    if let Page::Item(item) = page {
+        let mut fields: Vec<(String, String)> = item
+            .field_values()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        fields.sort();
+        matter.insert("fields", Value::Map(fields));
+    }
+    matter
+}
+
+/// Write every page in a tree to `dir` as Markdown. The tree should be hydrated first,
+/// or items will be written without content. Also writes a `Manifest` recording the
+/// page for each path, which lets `Client::sync_directory()` pick up from here.
+/// Returns a record of every file written.
+pub fn export_tree(tree: &PageTree, dir: &Path) -> NuclinoResult<Vec<ExportedPage>> {
+    let layout = Layout::plan(tree, "md", INDEX_NAME);
+    let mut exported: Vec<ExportedPage> = Vec::with_capacity(tree.len());
+    let mut manifest = Manifest::default();
+    std::fs::create_dir_all(dir)?;
+
+    for node in tree.depth_first() {
+        let page = node.page();
+        let Some(path) = layout.file(node.id()) else {
+            continue;
+        };
+        let mut document = page_front_matter(page).render();
+        let kind = match page {
+            Page::Item(item) => {
+                let body = item_body(item.content().map(String::as_str));
+                document.push('\n');
+                document.push_str(&body);
+                manifest.insert(
+                    &link_path(path),
+                    ManifestEntry {
+                        id: *page.id(),
+                        kind: PageKind::Item,
+                        hash: Some(synced_hash(page.title(), &body)),
+                        modified: Some(page.modified().to_string()),
+                    },
+                );
+                PageKind::Item
+            }
+            Page::Collection(_) => {
+                // A collection's index lists its children in order, with relative links.
+                document.push_str(&format!("\n# {}\n\n", page.title()));
+                let here = path.parent().unwrap_or(Path::new(""));
+                for child in node.children().iter().filter_map(|id| tree.get(id)) {
+                    if let Some(child_path) = layout.file(child.id()) {
+                        let target = link_target(&relative_path(here, child_path));
+                        document.push_str(&format!(
+                            "- [{}]({target})\n",
+                            link_text(child.page().title())
+                        ));
+                    }
+                }
+                if let Some(collection_dir) = layout.dir(node.id()) {
+                    manifest.insert(
+                        &link_path(collection_dir),
+                        ManifestEntry {
+                            id: *page.id(),
+                            kind: PageKind::Collection,
+                            hash: None,
+                            modified: Some(page.modified().to_string()),
+                        },
+                    );
+                }
+                PageKind::Collection
+            }
+        };
+        let full = dir.join(path);
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&full, document)?;
+        exported.push(ExportedPage {
+            id: *page.id(),
+            kind,
+            path: path.clone(),
+            modified: page.modified().to_string(),
+        });
+    }
+    manifest.save(dir)?;
+    Ok(exported)
+}
+
+impl Client {
+    /// Download the attachments of every item in a hydrated tree that `include` accepts,
+    /// keyed by item id, for exporters that keep local copies of files.
+    pub(crate) fn tree_attachments<F>(
+        &self,
+        tree: &PageTree,
+        include: F,
+    ) -> NuclinoResult<Attachments>
+    where
+        F: Fn(&Uuid) -> bool,
+    {
+        let mut attachments = HashMap::new();
+        for node in tree.depth_first().filter(|node| include(node.id())) {
+            if let Page::Item(item) = node.page() {
+                if !item.content_meta().file_ids.is_empty() {
+                    attachments.insert(*item.id(), self.download_attachments(item)?);
+                }
+            }
+        }
+        Ok(attachments)
+    }
+
+    /// Export a workspace to a directory of Markdown files. Every item is fetched
+    /// individually to get its content, so this makes one request per item.
+    pub fn export_workspace(
+        &self,
+        workspace: &Uuid,
+        dir: &Path,
+    ) -> NuclinoResult<Vec<ExportedPage>> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        export_tree(&tree, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2), id(3), id(4)]),
+            item_full(
+                id(2),
+                "Deploy: production",
+                Some("# Deploy\n\nRun the thing."),
+                "2021-12-15T17:02:53.487Z",
+                &[],
+                &[("Status", "Current")],
+            ),
+            item(id(3), "deploy- production", Some("duplicate name")),
+            item(id(4), "index", Some("not the collection index")),
+            item(id(5), "CON", None),
+        ];
+        PageTree::build(workspace(&[id(1), id(5)]), pages).expect("sample tree should build")
+    }
+
+    #[test]
+    fn sanitizing() {
+        assert_eq!(sanitize_filename("a/b\\c: d?"), "a-b-c- d-");
+        assert_eq!(sanitize_filename("  ..  "), "Untitled");
+        assert_eq!(sanitize_filename("nul"), "nul_");
+        assert_eq!(
+            sanitize_filename("tabs\tand\nnewlines"),
+            "tabs and newlines"
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let tree = sample();
+        let layout = Layout::plan(&tree, "md", INDEX_NAME);
+        assert_eq!(
+            layout.file(&id(1)),
+            Some(&PathBuf::from("Runbooks/index.md"))
+        );
+        assert_eq!(
+            layout.file(&id(2)),
+            Some(&PathBuf::from("Runbooks/Deploy- production.md"))
+        );
+        assert_eq!(
+            layout.file(&id(3)),
+            Some(&PathBuf::from("Runbooks/deploy- production (2).md"))
+        );
+        assert_eq!(
+            layout.file(&id(4)),
+            Some(&PathBuf::from("Runbooks/index (2).md"))
+        );
+        assert_eq!(layout.file(&id(5)), Some(&PathBuf::from("CON_.md")));
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            relative_path(Path::new("a/b"), Path::new("a/c/d.md")),
+            PathBuf::from("../c/d.md")
+        );
+        assert_eq!(
+            relative_path(Path::new(""), Path::new("x.md")),
+            PathBuf::from("x.md")
+        );
+        assert_eq!(
+            link_target(Path::new("../My Page (2).md")),
+            "../My%20Page%20%282%29.md"
+        );
+        assert_eq!(
+            link_target(Path::new("C# tips/What? [draft].md")),
+            "C%23%20tips/What%3F%20%5Bdraft%5D.md"
+        );
+    }
+
+    #[test]
+    fn escaping_link_text() {
+        assert_eq!(link_text("Deploy [prod]"), r"Deploy \[prod\]");
+        assert_eq!(link_text("*Draft* notes_v2"), r"\*Draft\* notes\_v2");
+        assert_eq!(link_text(r"C:\ and `code`"), r"C:\\ and \`code\`");
+    }
+
+    #[test]
+    fn exporting() {
+        let dir = std::env::temp_dir().join(format!("nuclino-export-{}", std::process::id()));
+        let exported = export_tree(&sample(), &dir).expect("export should succeed");
+        assert_eq!(exported.len(), 5);
+
+        let deploy = std::fs::read_to_string(dir.join("Runbooks/Deploy- production.md"))
+            .expect("the deploy page should have been written");
+        let expected = format!(
+            "---\nid: \"{}\"\nobject: \"item\"\ntitle: \"Deploy: production\"\n",
+            id(2)
+        );
+        assert!(deploy.starts_with(&expected));
+        assert!(deploy.contains("fields:\n  \"Status\": \"Current\"\n---\n"));
+        assert!(deploy.ends_with("---\n\n# Deploy\n\nRun the thing.\n"));
+
+        let index = std::fs::read_to_string(dir.join("Runbooks/index.md"))
+            .expect("the collection index should have been written");
+        assert!(index.contains("- [Deploy: production](Deploy-%20production.md)\n"));
+
+        let manifest = Manifest::load(&dir).expect("export should write a manifest");
+        let entry = manifest
+            .get("Runbooks/Deploy- production.md")
+            .expect("the deploy page should be in the manifest");
+        assert_eq!(entry.id, id(2));
+        assert_eq!(
+            entry.hash.as_deref(),
+            Some(synced_hash("Deploy: production", "# Deploy\n\nRun the thing.\n").as_str())
+        );
+        assert_eq!(manifest.get("Runbooks").map(|e| e.id), Some(id(1)));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,713 @@
+//! A small expression language for filtering pages by their metadata and field values,
+//! like `modified < 2026-01-01 AND field("Status") = "Deprecated" AND title ~ "runbook"`.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::{NuclinoError, NuclinoResult, Page};
+
+/// A parsed filter over pages. Comparisons are joined with `AND`, `OR`, and `NOT`, and
+/// grouped with parentheses; `AND` binds tighter than `OR`. Keywords ignore case.
+///
+/// A comparison is a field, an operator, and a value. Values with spaces or
+/// parentheses go in double quotes, with `\"` for a quote inside them.
+///
+/// - `created`, `modified`: ISO-8601 times, from a year down to fractions of a second,
+///   with an optional `Z` or `+hh:mm` offset; a time without one is UTC, like
+///   Nuclino's. A value stands for the whole span its precision gives, so
+///   `modified = 2026-01-01` matches anything that day, `modified < 2026-01` means
+///   before January, and `modified = 2026-01-01T14:00+02:00` matches the minute from
+///   noon UTC. `=`, `!=`, `<`, `<=`, `>`, `>=`.
+/// - `created_by`, `workspace`: ids. `=` and `!=`.
+/// - `title`: `~` for contains; `=` and `!=` ignoring case; and the orderings.
+/// - `field("Status")`: a field value, with the same operators as `title`. Values that
+///   are both numbers are ordered as numbers. A page without the field, including
+///   every collection, matches only `!=`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageFilter {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Compare(Comparison),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Comparison {
+    Time {
+        created: bool,
+        operator: Operator,
+        span: TimeSpan,
+    },
+    Id {
+        created_by: bool,
+        equal: bool,
+        value: Uuid,
+    },
+    Text {
+        field: Option<String>,
+        operator: Operator,
+        value: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+}
+
+impl Operator {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::NotEqual => ordering != Ordering::Equal,
+            Operator::Less => ordering == Ordering::Less,
+            Operator::LessOrEqual => ordering != Ordering::Greater,
+            Operator::Greater => ordering == Ordering::Greater,
+            Operator::GreaterOrEqual => ordering != Ordering::Less,
+            Operator::Contains => false,
+        }
+    }
+}
+
+/// The span of time an ISO-8601 time stands for, in nanoseconds since the Unix epoch:
+/// from its start to the start of the next time at the same precision. `2026-01` is
+/// all of January, and `2026-01-01T12:00:00.000Z` is one millisecond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TimeSpan {
+    start: i128,
+    end: i128,
+}
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+impl TimeSpan {
+    fn parse(text: &str) -> Option<Self> {
+        let (date, clock) = match text.split_once(['T', 't', ' ']) {
+            Some((date, clock)) => (date, Some(clock)),
+            None => (text, None),
+        };
+        let mut parts = date.split('-');
+        let year = number(parts.next()?, 4)?;
+        let month = optional(parts.next())?;
+        let day = optional(parts.next())?;
+        if parts.next().is_some() || !(1..=12).contains(&month.unwrap_or(1)) {
+            return None;
+        }
+        if !(1..=days_in_month(year, month.unwrap_or(1))).contains(&day.unwrap_or(1)) {
+            return None;
+        }
+        let days = days_from_civil(year, month.unwrap_or(1), day.unwrap_or(1));
+
+        let Some(clock) = clock else {
+            let next = match (month, day) {
+                (None, _) | (Some(12), None) => days_from_civil(year + 1, 1, 1),
+                (Some(month), None) => days_from_civil(year, month + 1, 1),
+                (Some(_), Some(_)) => days + 1,
+            };
+            return Some(Self {
+                start: i128::from(days) * 86_400 * NANOS_PER_SECOND,
+                end: i128::from(next) * 86_400 * NANOS_PER_SECOND,
+            });
+        };
+        day?;
+
+        let (clock, offset) = split_offset(clock)?;
+        let (clock, fraction) = match clock.split_once(['.', ',']) {
+            Some((clock, fraction)) => (clock, Some(fraction)),
+            None => (clock, None),
+        };
+        let mut fields = clock.split(':');
+        let hour = number(fields.next()?, 2)?;
+        let minute = optional(fields.next())?;
+        let second = optional(fields.next())?;
+        if fields.next().is_some()
+            || hour > 23
+            || minute.unwrap_or(0) > 59
+            || second.unwrap_or(0) > 60
+            || (fraction.is_some() && second.is_none())
+        {
+            return None;
+        }
+        let seconds = i128::from(days) * 86_400
+            + i128::from(hour) * 3600
+            + i128::from(minute.unwrap_or(0)) * 60
+            + i128::from(second.unwrap_or(0))
+            - i128::from(offset);
+        let mut start = seconds * NANOS_PER_SECOND;
+        let width = match (minute, second, fraction) {
+            (None, _, _) => 3600 * NANOS_PER_SECOND,
+            (Some(_), None, _) => 60 * NANOS_PER_SECOND,
+            (Some(_), Some(_), None) => NANOS_PER_SECOND,
+            (Some(_), Some(_), Some(fraction)) => {
+                if fraction.is_empty() || fraction.len() > 9 {
+                    return None;
+                }
+                let padding = 9 - fraction.len() as u32;
+                start += i128::from(number(fraction, fraction.len())?) * 10i128.pow(padding);
+                10i128.pow(padding)
+            }
+        };
+        Some(Self {
+            start,
+            end: start + width,
+        })
+    }
+}
+
+/// A run of exactly `width` ASCII digits as a number.
+fn number(text: &str, width: usize) -> Option<i64> {
+    if text.len() != width || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// A two-digit part of a time that may be left off: `Some(None)` if it is, and `None`
+/// if it's malformed.
+fn optional(text: Option<&str>) -> Option<Option<i64>> {
+    match text {
+        Some(text) => number(text, 2).map(Some),
+        None => Some(None),
+    }
+}
+
+/// Split a time of day from its UTC offset, giving the offset in seconds. A time
+/// without one is UTC.
+fn split_offset(clock: &str) -> Option<(&str, i64)> {
+    if let Some(clock) = clock.strip_suffix(['Z', 'z']) {
+        return Some((clock, 0));
+    }
+    let Some(at) = clock.rfind(['+', '-']) else {
+        return Some((clock, 0));
+    };
+    let sign = if clock[at..].starts_with('-') { -1 } else { 1 };
+    let offset = clock[at + 1..].replace(':', "");
+    let hours = number(offset.get(..2)?, 2)?;
+    let minutes = match offset.get(2..) {
+        Some("") => 0,
+        Some(minutes) => number(minutes, 2)?,
+        None => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some((&clock[..at], sign * (hours * 3600 + minutes * 60)))
+}
+
+/// How many days a month has in the proleptic Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+impl PageFilter {
+    /// Parse a filter expression.
+    pub fn parse(expression: &str) -> NuclinoResult<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+            end: expression.len(),
+        };
+        let expression = parser.or()?;
+        if let Some((at, token)) = parser.tokens.get(parser.position) {
+            return Err(invalid(*at, format!("unexpected {token}")));
+        }
+        Ok(Self { expression })
+    }
+
+    /// Whether a page passes the filter.
+    pub fn matches(&self, page: &Page) -> bool {
+        evaluate(&self.expression, page)
+    }
+}
+
+impl FromStr for PageFilter {
+    type Err = NuclinoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+fn evaluate(expression: &Expression, page: &Page) -> bool {
+    match expression {
+        Expression::And(left, right) => evaluate(left, page) && evaluate(right, page),
+        Expression::Or(left, right) => evaluate(left, page) || evaluate(right, page),
+        Expression::Not(inner) => !evaluate(inner, page),
+        Expression::Compare(comparison) => compare(comparison, page),
+    }
+}
+
+fn compare(comparison: &Comparison, page: &Page) -> bool {
+    match comparison {
+        Comparison::Time {
+            created,
+            operator,
+            span,
+        } => {
+            let time = if *created {
+                page.created()
+            } else {
+                page.modified()
+            };
+            let Some(time) = TimeSpan::parse(time) else {
+                return false;
+            };
+            let ordering = if time.start < span.start {
+                Ordering::Less
+            } else if time.start >= span.end {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            };
+            operator.accepts(ordering)
+        }
+        Comparison::Id {
+            created_by,
+            equal,
+            value,
+        } => {
+            let id = if *created_by {
+                page.created_by()
+            } else {
+                page.workspace()
+            };
+            (id == value) == *equal
+        }
+        Comparison::Text {
+            field,
+            operator,
+            value,
+        } => {
+            let text = match (field, page) {
+                (None, _) => page.title(),
+                (Some(name), Page::Item(item)) => match item
+                    .field_values()
+                    .iter()
+                    .find(|(field, _)| field.eq_ignore_ascii_case(name))
+                {
+                    Some((_, text)) => text.as_str(),
+                    None => return *operator == Operator::NotEqual,
+                },
+                (Some(_), Page::Collection(_)) => return *operator == Operator::NotEqual,
+            };
+            let text = text.to_lowercase();
+            if *operator == Operator::Contains {
+                return text.contains(value.as_str());
+            }
+            let ordering = match (text.trim().parse::<f64>(), value.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.total_cmp(&b),
+                _ => text.as_str().cmp(value.as_str()),
+            };
+            operator.accepts(ordering)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Operator(Operator),
+    Word(String),
+    Quoted(String),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::Operator(_) => write!(f, "operator"),
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(text) => write!(f, "\"{text}\""),
+        }
+    }
+}
+
+fn invalid(position: usize, message: String) -> NuclinoError {
+    NuclinoError::InvalidFilter { position, message }
+}
+
+fn tokenize(expression: &str) -> NuclinoResult<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let next_is_equals = matches!(chars.peek(), Some((_, '=')));
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Operator(Operator::Contains),
+            '=' => Token::Operator(Operator::Equal),
+            '!' if next_is_equals => Token::Operator(Operator::NotEqual),
+            '<' if next_is_equals => Token::Operator(Operator::LessOrEqual),
+            '>' if next_is_equals => Token::Operator(Operator::GreaterOrEqual),
+            '<' => Token::Operator(Operator::Less),
+            '>' => Token::Operator(Operator::Greater),
+            '!' => return Err(invalid(at, "'!' must be followed by '='".to_string())),
+            '"' => {
+                let mut text = String::new();
+                let mut closed = false;
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                text.push(escaped);
+                            }
+                        }
+                        _ => text.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(invalid(at, "unterminated quote".to_string()));
+                }
+                Token::Quoted(text)
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.peek().copied() {
+                    if c.is_whitespace() || "()\"=!<>~".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        if matches!(
+            token,
+            Token::Operator(Operator::NotEqual | Operator::LessOrEqual | Operator::GreaterOrEqual)
+        ) {
+            chars.next();
+        }
+        tokens.push((at, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// The length of the expression, for errors at its end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> NuclinoResult<(usize, Token)> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| invalid(self.end, "unexpected end of filter".to_string()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> NuclinoResult<Expression> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            left = Expression::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> NuclinoResult<Expression> {
+        let mut left = self.not()?;
+        while self.keyword("and") {
+            left = Expression::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> NuclinoResult<Expression> {
+        if self.keyword("not") {
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            let inner = self.or()?;
+            return match self.next()? {
+                (_, Token::Close) => Ok(inner),
+                (at, token) => Err(invalid(at, format!("expected ')' but found {token}"))),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> NuclinoResult<Expression> {
+        let (at, token) = self.next()?;
+        let Token::Word(name) = token else {
+            return Err(invalid(
+                at,
+                format!("expected a field name but found {token}"),
+            ));
+        };
+        let name = name.to_lowercase();
+        let field = if name == "field" {
+            let (open_at, open) = self.next()?;
+            if open != Token::Open {
+                return Err(invalid(open_at, "expected '(' after field".to_string()));
+            }
+            let field = self.value()?;
+            let (close_at, close) = self.next()?;
+            if close != Token::Close {
+                return Err(invalid(
+                    close_at,
+                    "expected ')' after the field name".to_string(),
+                ));
+            }
+            Some(field)
+        } else {
+            None
+        };
+
+        let (operator_at, operator) = match self.next()? {
+            (at, Token::Operator(operator)) => (at, operator),
+            (at, token) => {
+                return Err(invalid(
+                    at,
+                    format!("expected an operator but found {token}"),
+                ))
+            }
+        };
+        let value_at = self
+            .tokens
+            .get(self.position)
+            .map_or(self.end, |(at, _)| *at);
+        let value = self.value()?;
+
+        let comparison = match (name.as_str(), field) {
+            ("field", field) => Comparison::Text {
+                field,
+                operator,
+                value: value.to_lowercase(),
+            },
+            ("title", _) => Comparison::Text {
+                field: None,
+                operator,
+                value: value.to_lowercase(),
+            },
+            ("created" | "modified", _) => {
+                if operator == Operator::Contains {
+                    return Err(invalid(operator_at, format!("{name} can't use '~'")));
+                }
+                let Some(span) = TimeSpan::parse(&value) else {
+                    return Err(invalid(
+                        value_at,
+                        format!("'{value}' isn't an ISO-8601 time"),
+                    ));
+                };
+                Comparison::Time {
+                    created: name == "created",
+                    operator,
+                    span,
+                }
+            }
+            ("created_by" | "workspace", _) => {
+                let equal = match operator {
+                    Operator::Equal => true,
+                    Operator::NotEqual => false,
+                    _ => {
+                        return Err(invalid(
+                            operator_at,
+                            format!("{name} can only use '=' or '!='"),
+                        ))
+                    }
+                };
+                let value = Uuid::try_parse(&value)
+                    .map_err(|_| invalid(value_at, format!("'{value}' isn't an id")))?;
+                Comparison::Id {
+                    created_by: name == "created_by",
+                    equal,
+                    value,
+                }
+            }
+            _ => return Err(invalid(at, format!("unknown field '{name}'"))),
+        };
+        Ok(Expression::Compare(comparison))
+    }
+
+    fn value(&mut self) -> NuclinoResult<String> {
+        match self.next()? {
+            (_, Token::Word(word) | Token::Quoted(word)) => Ok(word),
+            (at, token) => Err(invalid(at, format!("expected a value but found {token}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn pages() -> Vec<Page> {
+        vec![
+            item_full(
+                id(1),
+                "Deploy runbook",
+                None,
+                "2025-11-02T09:00:00.000Z",
+                &[],
+                &[("Status", "Deprecated"), ("Priority", "10")],
+            ),
+            item_full(
+                id(2),
+                "Rollback Runbook",
+                None,
+                "2026-01-01T12:00:00.000Z",
+                &[],
+                &[("Status", "Current"), ("Priority", "9")],
+            ),
+            collection(id(3), "Runbooks", &[id(1), id(2)]),
+        ]
+    }
+
+    fn matching(filter: &str) -> Vec<Uuid> {
+        let filter = PageFilter::parse(filter).expect("filter should parse");
+        pages()
+            .iter()
+            .filter(|page| filter.matches(page))
+            .map(|page| *page.id())
+            .collect()
+    }
+
+    #[test]
+    fn filtering() {
+        assert_eq!(
+            matching(
+                r#"modified < 2026-01-01 AND field("Status") = "deprecated" AND title ~ "runbook""#
+            ),
+            vec![id(1)]
+        );
+        assert_eq!(matching("modified = 2026-01-01"), vec![id(2)]);
+        assert_eq!(matching("modified >= 2026-01-01"), vec![id(2)]);
+        assert_eq!(matching("modified < 2026"), vec![id(1), id(3)]);
+        assert_eq!(matching("field(priority) > 9"), vec![id(1)]);
+        assert_eq!(matching("field(Status) != current"), vec![id(1), id(3)]);
+        assert_eq!(
+            matching("not title ~ runbook or title = RUNBOOKS"),
+            vec![id(3)]
+        );
+        assert_eq!(
+            matching("title ~ deploy or (title ~ rollback and not field(\"Status\") = current)"),
+            vec![id(1)]
+        );
+        assert_eq!(
+            matching(&format!(
+                "created_by = {USER_ID} and workspace != {WORKSPACE_ID}"
+            )),
+            Vec::<Uuid>::new()
+        );
+        assert_eq!(matching(&format!("workspace = {WORKSPACE_ID}")).len(), 3);
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(matching("modified = 2026-01-01T12:00:00Z"), vec![id(2)]);
+        assert_eq!(matching("modified = 2026-01-01T12:00:00.000Z"), vec![id(2)]);
+        assert!(matching("modified = 2026-01-01T12:00:00.5Z").is_empty());
+        assert_eq!(
+            matching("modified < 2026-01-01T12:00:00.000Z"),
+            vec![id(1), id(3)]
+        );
+        assert_eq!(matching("modified <= 2026-01-01T12:00:00.000Z").len(), 3);
+        assert_eq!(
+            matching("modified = 2026-01-01T14:00:00+02:00"),
+            vec![id(2)]
+        );
+        assert_eq!(matching("modified > 2026-01-01T13:00+02:00"), vec![id(2)]);
+        assert_eq!(
+            matching("modified <= 2026-01-01T06:59-05:00"),
+            vec![id(1), id(3)]
+        );
+
+        let leap = TimeSpan::parse("2024-02").expect("a month should parse");
+        assert_eq!(leap.end - leap.start, 29 * 86_400 * NANOS_PER_SECOND);
+        let december = TimeSpan::parse("2025-12").expect("a month should parse");
+        assert_eq!(
+            Some(december.end),
+            TimeSpan::parse("2026").map(|span| span.start)
+        );
+        assert_eq!(
+            TimeSpan::parse("1970-01-01T00:00:01.5Z"),
+            Some(TimeSpan {
+                start: 1_500_000_000,
+                end: 1_600_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let position = |filter: &str| match PageFilter::parse(filter) {
+            Err(NuclinoError::InvalidFilter { position, .. }) => position,
+            other => panic!("expected a filter error for {filter}, got {other:?}"),
+        };
+        assert_eq!(position("title"), 5);
+        assert_eq!(position("owner = me"), 0);
+        assert_eq!(position("title ~ a b"), 10);
+        assert_eq!(position("(title ~ a"), 10);
+        assert_eq!(position("title ~ \"open"), 8);
+        assert_eq!(position("modified ~ 2026"), 9);
+        assert_eq!(position("modified < yesterday"), 11);
+        assert_eq!(position("modified < 2026-13-01"), 11);
+        assert_eq!(position("modified < 2026-02-31"), 11);
+        assert_eq!(position("modified < 2026-02-29"), 11);
+        assert_eq!(position("modified < 2026-04-31"), 11);
+        assert_eq!(position("modified < 1900-02-29"), 11);
+        assert!(PageFilter::parse("modified < 2024-02-29").is_ok());
+        assert!(PageFilter::parse("modified < 2000-02-29").is_ok());
+        assert_eq!(position("modified < 2026-01-01T25:00Z"), 11);
+        assert_eq!(position("workspace = general"), 12);
+        assert_eq!(position("created_by > x"), 11);
+        assert_eq!(position("title ! x"), 6);
+        assert!("title = x".parse::<PageFilter>().is_ok());
+    }
+}
@@ -0,0 +1,183 @@
+//! Test data builders. The API types have private fields, so tests outside `types.rs`
+//! build them the same way the client does: by deserializing Nuclino-shaped json.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{Client, File, Page, Workspace};
+
+pub const WORKSPACE_ID: &str = "127a8c4a-b3c6-4a42-8fef-b6c521e6c8cf";
+pub const USER_ID: &str = "2e96f3bb-c742-4164-af2c-151ab2fd346b";
+
+pub fn workspace(children: &[Uuid]) -> Workspace {
+    serde_json::from_value(json!({
+        "object": "workspace",
+        "id": WORKSPACE_ID,
+        "teamId": "020f9737-7b21-442b-85eb-bd420e5593b2",
+        "name": "General",
+        "createdAt": "2021-12-15T15:54:23.598Z",
+        "createdUserId": USER_ID,
+        "fields": [],
+        "childIds": children,
+    }))
+    .expect("fixture workspace should deserialize")
+}
+
+pub fn item(id: Uuid, title: &str, content: Option<&str>) -> Page {
+    item_full(id, title, content, "2021-12-15T17:02:53.487Z", &[], &[])
+}
+
+pub fn item_full(
+    id: Uuid,
+    title: &str,
+    content: Option<&str>,
+    modified: &str,
+    item_ids: &[Uuid],
+    fields: &[(&str, &str)],
+) -> Page {
+    let fields: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|(k, v)| (k.to_string(), json!(v)))
+        .collect();
+    serde_json::from_value(json!({
+        "object": "item",
+        "id": id,
+        "workspaceId": WORKSPACE_ID,
+        "url": format!("https://app.nuclino.com/t/b/{id}"),
+        "title": title,
+        "createdAt": "2021-12-15T15:55:19.527Z",
+        "createdUserId": USER_ID,
+        "lastUpdatedAt": modified,
+        "lastUpdatedUserId": USER_ID,
+        "fields": fields,
+        "content": content,
+        "contentMeta": { "itemIds": item_ids, "fileIds": [] },
+    }))
+    .expect("fixture item should deserialize")
+}
+
+pub fn collection(id: Uuid, title: &str, children: &[Uuid]) -> Page {
+    serde_json::from_value(json!({
+        "object": "collection",
+        "id": id,
+        "workspaceId": WORKSPACE_ID,
+        "url": format!("https://app.nuclino.com/t/b/{id}"),
+        "title": title,
+        "createdAt": "2021-12-15T17:02:56.276Z",
+        "createdUserId": USER_ID,
+        "lastUpdatedAt": "2021-12-15T17:03:00.389Z",
+        "lastUpdatedUserId": USER_ID,
+        "childIds": children,
+    }))
+    .expect("fixture collection should deserialize")
+}
+
+pub fn file(id: Uuid, item_id: Uuid, name: &str) -> File {
+    serde_json::from_value(json!({
+        "object": "file",
+        "id": id,
+        "itemId": item_id,
+        "fileName": name,
+        "createdAt": "2021-12-15T07:58:11.196Z",
+        "createdUserId": USER_ID,
+        "download": { "url": "https://example.com/expired", "expiresAt": "2021-12-15T08:08:49.931Z" }
+    }))
+    .expect("fixture file should deserialize")
+}
+
+/// A deterministic id for test page number `n`.
+pub fn id(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+/// A local http server that answers one request per response, in order, with each
+/// status and json body. Returns the base url to give a `Client`, and a handle that
+/// yields the request lines it received, like `GET /v0/items/... HTTP/1.1`.
+pub fn serve(responses: Vec<(u16, serde_json::Value)>) -> (String, JoinHandle<Vec<String>>) {
+    let (url, handle) = serve_capturing(responses);
+    let lines = std::thread::spawn(move || {
+        handle
+            .join()
+            .expect("the server should finish")
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect()
+    });
+    (url, lines)
+}
+
+/// Like `serve()`, but the handle yields each request's body along with its line.
+pub fn serve_capturing(
+    responses: Vec<(u16, serde_json::Value)>,
+) -> (String, JoinHandle<Vec<(String, String)>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("binding a local port should work");
+    let url = format!(
+        "http://{}",
+        listener
+            .local_addr()
+            .expect("the listener should have an address")
+    );
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().expect("the client should connect");
+            let mut reader = BufReader::new(stream.try_clone().expect("cloning a stream"));
+            let mut line = String::new();
+            reader
+                .read_line(&mut line)
+                .expect("reading the request line");
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("reading a header");
+                if header.trim_end().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+            }
+            let mut request_body = vec![0; length];
+            reader
+                .read_exact(&mut request_body)
+                .expect("reading the body");
+            requests.push((
+                line.trim_end().to_string(),
+                String::from_utf8_lossy(&request_body).to_string(),
+            ));
+            let body = body.to_string();
+            write!(
+                stream,
+                "HTTP/1.1 {status} Canned\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .expect("writing the response");
+        }
+        requests
+    });
+    (url, handle)
+}
+
+/// A client for a `serve()` url. Real clients insist on https, which the test server
+/// doesn't speak.
+pub fn local_client(url: &str) -> Client {
+    Client {
+        apikey: "test-key".to_string(),
+        baseurl: url.to_string(),
+        client: ureq::AgentBuilder::new().build(),
+    }
+}
+
+/// The error body Nuclino sends with a 404.
+pub fn not_found() -> (u16, serde_json::Value) {
+    (
+        404,
+        json!({ "status": "fail", "message": "Item not found" }),
+    )
+}
@@ -0,0 +1,279 @@
+//! Reading and writing the YAML front matter at the top of Markdown files. We write
+//! a small subset of YAML: string scalars, lists of strings, and one level of
+//! string-to-string maps. Strings are always written double-quoted using json escaping,
+//! which is also valid YAML. The reader understands the same subset, plus the plain and
+//! single-quoted scalars people write by hand.
+
+/// A single front matter value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Str(String),
+    List(Vec<String>),
+    Map(Vec<(String, String)>),
+}
+
+/// An ordered set of front matter entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct FrontMatter {
+    entries: Vec<(String, Value)>,
+}
+
+impl FrontMatter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, value: Value) -> &mut Self {
+        self.entries.retain(|(k, _)| k != key);
+        self.entries.push((key.to_string(), value));
+        self
+    }
+
+    pub(crate) fn string(&mut self, key: &str, value: &str) -> &mut Self {
+        self.insert(key, Value::Str(value.to_string()))
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub(crate) fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key) {
+            Some(Value::Str(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Render this front matter, including the `---` fences and a trailing newline.
+    pub(crate) fn render(&self) -> String {
+        let mut out = String::from("---\n");
+        for (key, value) in &self.entries {
+            match value {
+                Value::Str(s) => out.push_str(&format!("{}: {}\n", quote_key(key), quote(s))),
+                Value::List(items) if items.is_empty() => {
+                    out.push_str(&format!("{}: []\n", quote_key(key)))
+                }
+                Value::List(items) => {
+                    out.push_str(&format!("{}:\n", quote_key(key)));
+                    for item in items {
+                        out.push_str(&format!("  - {}\n", quote(item)));
+                    }
+                }
+                Value::Map(pairs) if pairs.is_empty() => {
+                    out.push_str(&format!("{}: {{}}\n", quote_key(key)))
+                }
+                Value::Map(pairs) => {
+                    out.push_str(&format!("{}:\n", quote_key(key)));
+                    for (k, v) in pairs {
+                        out.push_str(&format!("  {}: {}\n", quote(k), quote(v)));
+                    }
+                }
+            }
+        }
+        out.push_str("---\n");
+        out
+    }
+
+    /// Split a document into its front matter, if it has any, and the body that follows.
+    /// Front matter must start on the first line. Anything we can't make sense of is
+    /// skipped rather than treated as an error, because hand-written files have all sorts
+    /// of YAML in them.
+    pub(crate) fn split(document: &str) -> (Option<FrontMatter>, &str) {
+        let Some(rest) = document
+            .strip_prefix("---\n")
+            .or_else(|| document.strip_prefix("---\r\n"))
+        else {
+            return (None, document);
+        };
+        let mut offset = 0;
+        let mut end: Option<(usize, usize)> = None;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == "---" || line.trim_end() == "..." {
+                end = Some((offset, offset + line.len()));
+                break;
+            }
+            offset += line.len();
+        }
+        let Some((yaml_end, body_start)) = end else {
+            return (None, document);
+        };
+        (Some(parse(&rest[..yaml_end])), &rest[body_start..])
+    }
+}
+
+fn parse(yaml: &str) -> FrontMatter {
+    let mut matter = FrontMatter::new();
+    let mut current: Option<(String, Value)> = None;
+    for line in yaml.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        let trimmed = line.trim();
+        if indented {
+            match current.as_mut() {
+                Some((_, Value::List(items))) if trimmed.starts_with('-') => {
+                    items.push(unquote(trimmed[1..].trim()));
+                }
+                Some((_, value @ Value::List(_))) => {
+                    // An empty value followed by `key: value` lines is a map, not a list.
+                    if let (Value::List(items), Some((k, v))) = (&*value, split_pair(trimmed)) {
+                        if items.is_empty() {
+                            *value = Value::Map(vec![(k, unquote(v))]);
+                        }
+                    }
+                }
+                Some((_, Value::Map(pairs))) => {
+                    if let Some((k, v)) = split_pair(trimmed) {
+                        pairs.push((k, unquote(v)));
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if let Some((key, value)) = current.take() {
+            matter.insert(&key, value);
+        }
+        let Some((key, value)) = split_pair(trimmed) else {
+            continue;
+        };
+        let value = value.trim();
+        let parsed = if value.is_empty() {
+            // The type of an empty value depends on the lines that follow.
+            None
+        } else if value == "[]" {
+            Some(Value::List(Vec::new()))
+        } else if value == "{}" {
+            Some(Value::Map(Vec::new()))
+        } else if value.starts_with('[') && value.ends_with(']') {
+            let inner = &value[1..value.len() - 1];
+            Some(Value::List(
+                inner.split(',').map(|s| unquote(s.trim())).collect(),
+            ))
+        } else {
+            Some(Value::Str(unquote(value)))
+        };
+        match parsed {
+            Some(v) => matter.insert(&key, v),
+            None => {
+                current = Some((key, Value::List(Vec::new())));
+                continue;
+            }
+        };
+    }
+    if let Some((key, value)) = current.take() {
+        matter.insert(&key, value);
+    }
+    matter
+}
+
+/// Split `key: value`, respecting a quoted key.
+fn split_pair(line: &str) -> Option<(String, &str)> {
+    if line.starts_with('"') {
+        let end = closing_quote(line)?;
+        let key = unquote(&line[..=end]);
+        let rest = line[end + 1..].trim_start().strip_prefix(':')?;
+        return Some((key, rest.trim()));
+    }
+    let (key, value) = line.split_once(':')?;
+    Some((key.trim().to_string(), value.trim()))
+}
+
+/// Find the byte offset of the quote closing a double-quoted string starting at 0.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s.replace('"', "'")))
+}
+
+fn quote_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+fn unquote(s: &str) -> String {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        if let Ok(unquoted) = serde_json::from_str::<String>(s) {
+            return unquoted;
+        }
+    }
+    if s.len() >= 2 && s.starts_with('\'') && s.ends_with('\'') {
+        return s[1..s.len() - 1].replace("''", "'");
+    }
+    s.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rendering() {
+        let mut matter = FrontMatter::new();
+        matter
+            .string("title", "A \"quoted\": title")
+            .insert(
+                "fields",
+                Value::Map(vec![("My date field".into(), "2025-01-20".into())]),
+            )
+            .insert("empty", Value::Map(vec![]))
+            .string("title", "Replaced");
+        assert_eq!(
+            matter.render(),
+            "---\nfields:\n  \"My date field\": \"2025-01-20\"\nempty: {}\ntitle: \"Replaced\"\n---\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut matter = FrontMatter::new();
+        matter
+            .string("title", "A \"quoted\": title")
+            .insert("tags", Value::List(vec!["one".into(), "two".into()]))
+            .insert(
+                "fields",
+                Value::Map(vec![("My date field".into(), "2025-01-20".into())]),
+            )
+            .insert("empty", Value::List(vec![]));
+        let doc = format!("{}# Body\n", matter.render());
+        let (parsed, body) = FrontMatter::split(&doc);
+        assert_eq!(parsed, Some(matter));
+        assert_eq!(body, "# Body\n");
+    }
+
+    #[test]
+    fn hand_written_yaml() {
+        let doc = "---\ntitle: Plain title\nauthor: 'Someone''s name'\n# comment\ntags: [a, b]\n---\nbody";
+        let (parsed, body) = FrontMatter::split(doc);
+        let parsed = parsed.expect("front matter should be found");
+        assert_eq!(parsed.get_str("title"), Some("Plain title"));
+        assert_eq!(parsed.get_str("author"), Some("Someone's name"));
+        assert_eq!(
+            parsed.get("tags"),
+            Some(&Value::List(vec!["a".into(), "b".into()]))
+        );
+        assert_eq!(body, "body");
+
+        let (none, body) = FrontMatter::split("# No front matter\n");
+        assert!(none.is_none());
+        assert_eq!(body, "# No front matter\n");
+    }
+}
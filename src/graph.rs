@@ -0,0 +1,322 @@
+//! The graph of links between pages, built from the `item_ids` in each item's content
+//! metadata: "what links here", orphaned pages, and clusters of pages that all link to
+//! one another.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use uuid::Uuid;
+
+use crate::{Client, NuclinoResult, Page};
+
+/// Links between the pages of a workspace. Add pages one at a time with `add_page()`, or
+/// all at once with `from_pages()`; adding a page again replaces what was known about
+/// it. Items must be hydrated, because only full item responses include content
+/// metadata.
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    /// Every page added, with its title.
+    titles: BTreeMap<Uuid, String>,
+    outlinks: BTreeMap<Uuid, BTreeSet<Uuid>>,
+    backlinks: BTreeMap<Uuid, BTreeSet<Uuid>>,
+    /// The children of each collection added.
+    children: BTreeMap<Uuid, Vec<Uuid>>,
+}
+
+impl LinkGraph {
+    /// An empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a graph from a set of pages.
+    pub fn from_pages<'a, I>(pages: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Page>,
+    {
+        let mut graph = Self::new();
+        for page in pages {
+            graph.add_page(page);
+        }
+        graph
+    }
+
+    /// Add a page to the graph, or update it if it's already there.
+    pub fn add_page(&mut self, page: &Page) {
+        let id = *page.id();
+        self.remove_page(&id);
+        self.titles.insert(id, page.title().to_string());
+        match page {
+            Page::Item(item) => {
+                let targets: BTreeSet<Uuid> = item
+                    .content_meta()
+                    .item_ids
+                    .iter()
+                    .copied()
+                    .filter(|target| *target != id)
+                    .collect();
+                for target in &targets {
+                    self.backlinks.entry(*target).or_default().insert(id);
+                }
+                self.outlinks.insert(id, targets);
+            }
+            Page::Collection(collection) => {
+                self.children.insert(id, collection.children().to_vec());
+            }
+        }
+    }
+
+    /// Remove a page and the links from it. Links to it from other pages remain, since
+    /// they're still in those pages' content.
+    pub fn remove_page(&mut self, id: &Uuid) {
+        self.titles.remove(id);
+        self.children.remove(id);
+        if let Some(targets) = self.outlinks.remove(id) {
+            for target in targets {
+                if let Some(sources) = self.backlinks.get_mut(&target) {
+                    sources.remove(id);
+                    if sources.is_empty() {
+                        self.backlinks.remove(&target);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether a page has been added.
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.titles.contains_key(id)
+    }
+
+    /// The title of a page that has been added.
+    pub fn title(&self, id: &Uuid) -> Option<&str> {
+        self.titles.get(id).map(String::as_str)
+    }
+
+    /// How many pages have been added.
+    pub fn len(&self) -> usize {
+        self.titles.len()
+    }
+
+    /// Whether the graph has no pages.
+    pub fn is_empty(&self) -> bool {
+        self.titles.is_empty()
+    }
+
+    /// The pages that link to this one, in id order.
+    pub fn backlinks(&self, id: &Uuid) -> Vec<Uuid> {
+        self.backlinks
+            .get(id)
+            .map(|sources| sources.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// The pages this one links to, in id order. These may include pages that were
+    /// never added to the graph, such as pages in other workspaces.
+    pub fn outlinks(&self, id: &Uuid) -> Vec<Uuid> {
+        self.outlinks
+            .get(id)
+            .map(|targets| targets.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Pages that nothing links to and that aren't in any collection, so the only way to
+    /// find them is at the top level of the workspace, in id order.
+    pub fn orphans(&self) -> Vec<Uuid> {
+        let contained: BTreeSet<&Uuid> = self.children.values().flatten().collect();
+        self.titles
+            .keys()
+            .filter(|id| !self.backlinks.contains_key(id) && !contained.contains(id))
+            .copied()
+            .collect()
+    }
+
+    /// Groups of two or more pages where every page can reach every other by following
+    /// links: the strongly connected components of the graph. Each cluster is sorted by
+    /// id, and clusters are ordered by their first id.
+    pub fn clusters(&self) -> Vec<Vec<Uuid>> {
+        let mut clusters: Vec<Vec<Uuid>> = strongly_connected(&self.outlinks)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+            .collect();
+        clusters.sort();
+        clusters
+    }
+}
+
+/// Tarjan's algorithm, written with an explicit stack so long chains of links can't
+/// overflow the call stack.
+fn strongly_connected(edges: &BTreeMap<Uuid, BTreeSet<Uuid>>) -> Vec<Vec<Uuid>> {
+    #[derive(Clone, Copy)]
+    struct Visit {
+        index: usize,
+        low: usize,
+        on_stack: bool,
+    }
+
+    let empty = BTreeSet::new();
+    let targets =
+        |id: &Uuid| -> Vec<Uuid> { edges.get(id).unwrap_or(&empty).iter().copied().collect() };
+    let mut visits: BTreeMap<Uuid, Visit> = BTreeMap::new();
+    let mut stack: Vec<Uuid> = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+
+    for root in edges.keys() {
+        if visits.contains_key(root) {
+            continue;
+        }
+        // Each frame is a node and the position of the next edge to follow from it.
+        let mut frames: Vec<(Uuid, Vec<Uuid>, usize)> = vec![(*root, targets(root), 0)];
+        visits.insert(
+            *root,
+            Visit {
+                index: next_index,
+                low: next_index,
+                on_stack: true,
+            },
+        );
+        next_index += 1;
+        stack.push(*root);
+
+        while let Some((node, node_targets, position)) = frames.last_mut() {
+            let node = *node;
+            if let Some(target) = node_targets.get(*position).copied() {
+                *position += 1;
+                match visits.get(&target).copied() {
+                    None => {
+                        visits.insert(
+                            target,
+                            Visit {
+                                index: next_index,
+                                low: next_index,
+                                on_stack: true,
+                            },
+                        );
+                        next_index += 1;
+                        stack.push(target);
+                        frames.push((target, targets(&target), 0));
+                    }
+                    Some(visit) if visit.on_stack => {
+                        if let Some(current) = visits.get_mut(&node) {
+                            current.low = current.low.min(visit.index);
+                        }
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            // Every edge from this node has been followed.
+            frames.pop();
+            let Some(visit) = visits.get(&node).copied() else {
+                continue;
+            };
+            if let Some((parent, _, _)) = frames.last() {
+                if let Some(parent_visit) = visits.get_mut(parent) {
+                    parent_visit.low = parent_visit.low.min(visit.low);
+                }
+            }
+            if visit.low == visit.index {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    if let Some(v) = visits.get_mut(&member) {
+                        v.on_stack = false;
+                    }
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+impl Client {
+    /// Build the link graph for a workspace. This fetches every item, so it makes one
+    /// request per item.
+    pub fn link_graph(&self, workspace: &Uuid) -> NuclinoResult<LinkGraph> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        Ok(LinkGraph::from_pages(
+            tree.depth_first().map(|node| node.page()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn linking(n: u128, targets: &[u128]) -> Page {
+        let targets: Vec<Uuid> = targets.iter().map(|t| id(*t)).collect();
+        item_full(
+            id(n),
+            &format!("Page {n}"),
+            Some(""),
+            "2021-12-15T17:02:53.487Z",
+            &targets,
+            &[],
+        )
+    }
+
+    fn sample() -> Vec<Page> {
+        vec![
+            collection(id(10), "Docs", &[id(1), id(2)]),
+            linking(1, &[2, 3]),
+            linking(2, &[1, 1, 2]),
+            linking(3, &[4]),
+            linking(4, &[3, 5]),
+            linking(5, &[99]),
+            linking(6, &[]),
+        ]
+    }
+
+    #[test]
+    fn links_both_ways() {
+        let graph = LinkGraph::from_pages(&sample());
+        assert_eq!(graph.len(), 7);
+        assert_eq!(graph.outlinks(&id(2)), vec![id(1)]);
+        assert_eq!(graph.backlinks(&id(1)), vec![id(2)]);
+        assert_eq!(graph.backlinks(&id(3)), vec![id(1), id(4)]);
+        assert_eq!(graph.backlinks(&id(99)), vec![id(5)]);
+        assert!(!graph.contains(&id(99)));
+        assert_eq!(graph.title(&id(6)), Some("Page 6"));
+    }
+
+    #[test]
+    fn orphans_and_clusters() {
+        let mut graph = LinkGraph::from_pages(&sample());
+        // The collection and page 6 have no links in and no parent.
+        assert_eq!(graph.orphans(), vec![id(6), id(10)]);
+        assert_eq!(
+            graph.clusters(),
+            vec![vec![id(1), id(2)], vec![id(3), id(4)]]
+        );
+
+        // Updating a page replaces its links.
+        graph.add_page(&linking(4, &[5]));
+        assert_eq!(graph.backlinks(&id(3)), vec![id(1)]);
+        assert_eq!(graph.clusters(), vec![vec![id(1), id(2)]]);
+        graph.remove_page(&id(1));
+        assert!(graph.backlinks(&id(2)).is_empty());
+        assert!(graph.clusters().is_empty());
+    }
+
+    #[test]
+    fn long_chains() {
+        let count = 5_000;
+        let pages: Vec<Page> = (0..count).map(|n| linking(n, &[(n + 1) % count])).collect();
+        let graph = LinkGraph::from_pages(&pages);
+        let clusters = graph.clusters();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].len(), count as usize);
+    }
+}
@@ -0,0 +1,206 @@
+//! Search result highlights. The server marks the matching words in each result's
+//! `highlight` with HTML tags; `Highlight` turns that into plain text and the ranges of
+//! the matches, ready to render in bold, in terminal colors, or however you like.
+
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::Item;
+
+/// The tags that mark a match. Any other tag is left in the text as written.
+const MATCH_TAGS: &[&str] = &["b", "strong", "em", "mark"];
+
+/// A highlight with its markup removed: the plain text, and the byte ranges in it of
+/// every match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Highlight {
+    text: String,
+    matches: Vec<Range<usize>>,
+}
+
+impl Highlight {
+    /// Parse a highlight. Text inside `<b>`, `<strong>`, `<em>`, or `<mark>` tags is a
+    /// match, and HTML entities are decoded. Anything that isn't one of those tags is
+    /// kept as text, so a highlight without markup is all text and no matches. A tag
+    /// left open matches to the end.
+    pub fn parse(raw: &str) -> Self {
+        let mut text = String::with_capacity(raw.len());
+        let mut matches: Vec<Range<usize>> = Vec::new();
+        let mut open: Option<usize> = None;
+        let mut depth = 0usize;
+        let mut rest = raw;
+        while let Some(c) = rest.chars().next() {
+            if c == '<' {
+                if let Some((closing, length)) = match_tag(rest) {
+                    rest = &rest[length..];
+                    if !closing {
+                        depth += 1;
+                        open.get_or_insert(text.len());
+                    } else if depth > 0 {
+                        depth -= 1;
+                        if depth == 0 {
+                            if let Some(start) = open.take() {
+                                push_match(&mut matches, start..text.len());
+                            }
+                        }
+                    }
+                    continue;
+                }
+            } else if c == '&' {
+                if let Some((decoded, length)) = entity(rest) {
+                    text.push(decoded);
+                    rest = &rest[length..];
+                    continue;
+                }
+            }
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        if let Some(start) = open {
+            push_match(&mut matches, start..text.len());
+        }
+        Self { text, matches }
+    }
+
+    /// The highlight as plain text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The byte ranges of the matches in `text()`, in order and not overlapping.
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    /// Whether anything in the highlight matched.
+    pub fn has_matches(&self) -> bool {
+        !self.matches.is_empty()
+    }
+
+    /// The text of each match.
+    pub fn matched_text(&self) -> Vec<&str> {
+        self.matches
+            .iter()
+            .map(|range| &self.text[range.clone()])
+            .collect()
+    }
+
+    /// The text with `before` and `after` around each match: `render("**", "**")` for
+    /// Markdown, or `render("\x1b[1m", "\x1b[0m")` for bold in a terminal.
+    pub fn render(&self, before: &str, after: &str) -> String {
+        let mut out = String::with_capacity(self.text.len());
+        let mut copied = 0;
+        for range in &self.matches {
+            out.push_str(&self.text[copied..range.start]);
+            out.push_str(before);
+            out.push_str(&self.text[range.clone()]);
+            out.push_str(after);
+            copied = range.end;
+        }
+        out.push_str(&self.text[copied..]);
+        out
+    }
+}
+
+impl Display for Highlight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Item {
+    /// The parsed highlight of a search result, if this item came from a search.
+    pub fn highlight(&self) -> Option<Highlight> {
+        self.highlight_text().map(|raw| Highlight::parse(raw))
+    }
+}
+
+/// Empty matches are dropped, and a match that touches the one before it joins it.
+fn push_match(matches: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    match matches.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => matches.push(range),
+    }
+}
+
+/// If `text` starts with one of the match tags, whether it's a closing tag and how long
+/// it is.
+fn match_tag(text: &str) -> Option<(bool, usize)> {
+    let end = text.find('>')?;
+    let inner = &text[1..end];
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, inner),
+    };
+    let name = inner
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default();
+    MATCH_TAGS
+        .iter()
+        .any(|tag| tag.eq_ignore_ascii_case(name))
+        .then_some((closing, end + 1))
+}
+
+/// If `text` starts with an HTML entity, the character and the entity's length.
+fn entity(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';').filter(|end| *end <= 10)?;
+    let name = &text[1..end];
+    let decoded = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((decoded, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_markup() {
+        let highlight = Highlight::parse(
+            "Run the <b>deploy</b> script, then <STRONG>roll</STRONG> <em>back</em>.",
+        );
+        assert_eq!(highlight.text(), "Run the deploy script, then roll back.");
+        assert_eq!(highlight.matched_text(), vec!["deploy", "roll", "back"]);
+        assert_eq!(
+            highlight.render("**", "**"),
+            "Run the **deploy** script, then **roll** **back**."
+        );
+
+        // Adjacent and nested tags make one match; entities are decoded.
+        let highlight = Highlight::parse("<mark>R&amp;D</mark><b>&#39;s</b> <b><em>plan</em></b>");
+        assert_eq!(highlight.text(), "R&D's plan");
+        assert_eq!(highlight.matches(), &[0..5, 6..10]);
+    }
+
+    #[test]
+    fn falling_back() {
+        let plain = Highlight::parse("if a < b && c > d then <br> &bogus; done");
+        assert_eq!(plain.text(), "if a < b && c > d then <br> &bogus; done");
+        assert!(!plain.has_matches());
+        assert_eq!(plain.render("[", "]"), plain.text());
+
+        // Stray closing tags are dropped, and a tag left open matches to the end.
+        let unbalanced = Highlight::parse("</b>one <b>two three");
+        assert_eq!(unbalanced.text(), "one two three");
+        assert_eq!(unbalanced.matched_text(), vec!["two three"]);
+        assert!(Highlight::parse("").text().is_empty());
+    }
+}
@@ -0,0 +1,446 @@
+//! Importing a local directory of Markdown files into a workspace. Directories become
+//! collections and `.md` files become items. A manifest file in the directory records
+//! which page each local path was imported as, so importing again updates those pages
+//! instead of creating duplicates.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::export::link_path;
+use crate::frontmatter::FrontMatter;
+use crate::{Client, ModifyItem, NewPageBuilder, NuclinoResult, PageKind, INDEX_NAME};
+
+/// The name of the manifest file written into an imported directory.
+pub static MANIFEST_NAME: &str = ".nuclino-manifest.json";
+
+/// One entry in a `Manifest`: the page a local path corresponds to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The id of the Nuclino page.
+    pub id: Uuid,
+    /// Whether the page is an item or a collection.
+    pub kind: PageKind,
+    /// A hash of the local file's title and content when it was last synced with Nuclino.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The page's last-modified time on Nuclino when it was last synced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+/// A mapping from local paths to Nuclino pages. Paths are relative to the directory
+/// holding the manifest and always use `/` as a separator. Items are keyed by file path
+/// and collections by directory path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pages: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Load the manifest from a directory. A missing manifest is an empty one.
+    pub fn load(dir: &Path) -> NuclinoResult<Self> {
+        let path = dir.join(MANIFEST_NAME);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let bytes = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Write the manifest into a directory.
+    pub fn save(&self, dir: &Path) -> NuclinoResult<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(dir.join(MANIFEST_NAME), json)?;
+        Ok(())
+    }
+
+    /// Look up the page for a local path.
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.pages.get(path)
+    }
+
+    /// Record the page for a local path.
+    pub fn insert(&mut self, path: &str, entry: ManifestEntry) {
+        self.pages.insert(path.to_string(), entry);
+    }
+
+    /// Forget a local path.
+    pub fn remove(&mut self, path: &str) -> Option<ManifestEntry> {
+        self.pages.remove(path)
+    }
+
+    /// Every path and page in the manifest, sorted by path.
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &ManifestEntry)> {
+        self.pages.iter()
+    }
+}
+
+/// A local file or directory, read and ready to import.
+#[derive(Debug, Clone)]
+pub struct LocalPage {
+    /// The path relative to the import root, with `/` separators.
+    pub path: String,
+    /// The title the page will have in Nuclino.
+    pub title: String,
+    /// Whether this becomes an item or a collection.
+    pub kind: PageKind,
+    /// The Markdown content for an item, without front matter. Always `None` for collections.
+    pub content: Option<String>,
+    /// A collection's children, in the order they'll be created.
+    pub children: Vec<LocalPage>,
+}
+
+/// Whether an imported page was newly created or an update of an earlier import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportAction {
+    /// The page didn't exist, so we created it.
+    Created,
+    /// The manifest knew about this page, so we updated it.
+    Updated,
+}
+
+/// A record of one page handled by an import.
+#[derive(Debug, Clone)]
+pub struct ImportedPage {
+    /// The local path, relative to the import root.
+    pub path: String,
+    /// The id of the Nuclino page.
+    pub id: Uuid,
+    /// What we did to it.
+    pub action: ImportAction,
+}
+
+/// Read a directory tree into the pages an import would create. Hidden files and
+/// anything that isn't a directory or a `.md` file are skipped. Item titles come from
+/// a `title` in the front matter, or else from a first-line `# Heading` (which is then
+/// removed from the content), or else from the file name. A directory's `index.md`
+/// supplies the collection's title the same way, and its links set the order of the
+/// directory's children; children it doesn't link to follow in name order. Index
+/// files aren't imported as pages, because collections can't have content.
+pub fn read_directory(dir: &Path) -> NuclinoResult<Vec<LocalPage>> {
+    Ok(read_children(dir, Path::new(""))?.0)
+}
+
+/// Read the pages in `root/relative`, returning them and the directory's index title, if any.
+fn read_children(root: &Path, relative: &Path) -> NuclinoResult<(Vec<LocalPage>, Option<String>)> {
+    let dir = root.join(relative);
+    let index_file = format!("{INDEX_NAME}.md");
+    let mut pages: Vec<LocalPage> = Vec::new();
+    let mut index: Option<String> = None;
+
+    let mut entries: Vec<std::fs::DirEntry> = std::fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|e| e.file_name().to_string_lossy().to_lowercase());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let child = relative.join(&name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let (children, title) = read_children(root, &child)?;
+            pages.push(LocalPage {
+                path: link_path(&child),
+                title: title.unwrap_or(name),
+                kind: PageKind::Collection,
+                content: None,
+                children,
+            });
+        } else if name == index_file {
+            index = Some(std::fs::read_to_string(entry.path())?);
+        } else if let Some(stem) = name.strip_suffix(".md") {
+            let text = std::fs::read_to_string(entry.path())?;
+            let (title, content) = title_and_body(&text);
+            pages.push(LocalPage {
+                path: link_path(&child),
+                title: title.unwrap_or_else(|| stem.to_string()),
+                kind: PageKind::Item,
+                content: Some(content),
+                children: Vec::new(),
+            });
+        }
+    }
+
+    let Some(index) = index else {
+        return Ok((pages, None));
+    };
+    let (title, body) = title_and_body(&index);
+    // Children linked from the index come first, in link order.
+    let mut ordered: Vec<LocalPage> = Vec::with_capacity(pages.len());
+    for target in link_targets(&body) {
+        let Ok(decoded) = urlencoding::decode(&target) else {
+            continue;
+        };
+        let linked = link_path(&relative.join(decoded.as_ref()));
+        let position = pages
+            .iter()
+            .position(|p| p.path == linked || format!("{}/{INDEX_NAME}.md", p.path) == linked);
+        if let Some(position) = position {
+            ordered.push(pages.remove(position));
+        }
+    }
+    ordered.append(&mut pages);
+    Ok((ordered, title))
+}
+
+/// Find a page title in front matter or a leading `# Heading`, and return it with the
+/// remaining content.
+fn title_and_body(text: &str) -> (Option<String>, String) {
+    let (matter, body) = FrontMatter::split(text);
+    if let Some(title) = matter.as_ref().and_then(|m| m.get_str("title")) {
+        return (
+            Some(title.to_string()),
+            body.trim_start_matches('\n').to_string(),
+        );
+    }
+    let trimmed = body.trim_start();
+    let first_line = trimmed.lines().next().unwrap_or_default();
+    if let Some(heading) = first_line.strip_prefix("# ") {
+        let rest = trimmed[first_line.len()..].trim_start_matches(['\r', '\n']);
+        return (Some(heading.trim().to_string()), rest.to_string());
+    }
+    (None, body.to_string())
+}
+
+/// The targets of every inline Markdown link in some text, in order.
+fn link_targets(text: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+        targets.push(rest[..end].trim().to_string());
+        rest = &rest[end + 1..];
+    }
+    targets
+}
+
+/// Where a page is going: the workspace's top level or inside a collection.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Workspace(Uuid),
+    Parent(Uuid),
+}
+
+impl Client {
+    /// Import a local directory tree into a workspace. Pages recorded in the directory's
+    /// manifest are updated in place; everything else is created, in sibling order, and
+    /// added to the manifest. The manifest is saved after every page created, so an
+    /// interrupted import can be safely re-run. Pages that already exist aren't moved,
+    /// because the Nuclino API has no way to do that.
+    pub fn import_directory(
+        &self,
+        dir: &Path,
+        workspace: &Uuid,
+    ) -> NuclinoResult<Vec<ImportedPage>> {
+        let pages = read_directory(dir)?;
+        let mut manifest = Manifest::load(dir)?;
+        let mut imported: Vec<ImportedPage> = Vec::new();
+        self.import_pages(
+            &pages,
+            Location::Workspace(*workspace),
+            dir,
+            &mut manifest,
+            &mut imported,
+        )?;
+        manifest.save(dir)?;
+        Ok(imported)
+    }
+
+    fn import_pages(
+        &self,
+        pages: &[LocalPage],
+        location: Location,
+        dir: &Path,
+        manifest: &mut Manifest,
+        imported: &mut Vec<ImportedPage>,
+    ) -> NuclinoResult<()> {
+        for (index, local) in pages.iter().enumerate() {
+            let existing = manifest
+                .get(&local.path)
+                .filter(|entry| entry.kind == local.kind)
+                .map(|entry| entry.id);
+            let updated = match existing {
+                Some(id) => {
+                    let changes = ModifyItem {
+                        title: Some(local.title.clone()),
+                        content: local.content.clone(),
+                    };
+                    match self.page_update(&id, &changes) {
+                        Ok(_) => Some(id),
+                        // The page was deleted since the last import; make a new one.
+                        Err(e) if e.is_not_found() => None,
+                        Err(e) => return Err(e),
+                    }
+                }
+                None => None,
+            };
+            let (id, action) = match updated {
+                Some(id) => (id, ImportAction::Updated),
+                None => {
+                    let mut builder = match local.kind {
+                        PageKind::Item => NewPageBuilder::item(),
+                        PageKind::Collection => NewPageBuilder::collection(),
+                    };
+                    builder.title(&local.title).index(index);
+                    if let Some(content) = local.content.as_ref() {
+                        builder.content(content);
+                    }
+                    match location {
+                        Location::Workspace(id) => builder.workspace(&id),
+                        Location::Parent(id) => builder.parent(&id),
+                    };
+                    let page = self.page_create(builder.try_build()?)?;
+                    manifest.insert(
+                        &local.path,
+                        ManifestEntry {
+                            id: *page.id(),
+                            kind: local.kind.clone(),
+                            hash: None,
+                            modified: None,
+                        },
+                    );
+                    manifest.save(dir)?;
+                    (*page.id(), ImportAction::Created)
+                }
+            };
+            imported.push(ImportedPage {
+                path: local.path.clone(),
+                id,
+                action,
+            });
+            if !local.children.is_empty() {
+                self.import_pages(
+                    &local.children,
+                    Location::Parent(id),
+                    dir,
+                    manifest,
+                    imported,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let full = root.join(path);
+        std::fs::create_dir_all(full.parent().expect("test paths have parents"))
+            .expect("creating test directories should work");
+        std::fs::write(full, text).expect("writing test files should work");
+    }
+
+    #[test]
+    fn reading_a_directory() {
+        let root = std::env::temp_dir().join(format!("nuclino-import-{}", std::process::id()));
+        write(&root, "Welcome.md", "Hello there.\n");
+        write(
+            &root,
+            "Runbooks/index.md",
+            "---\ntitle: \"Run Books\"\n---\n- [Rollback](Roll%20back.md)\n",
+        );
+        write(
+            &root,
+            "Runbooks/Deploy.md",
+            "# Deploying to production\n\nSteps.\n",
+        );
+        write(
+            &root,
+            "Runbooks/Roll back.md",
+            "---\ntitle: Rolling back\n---\n\nUndo.\n",
+        );
+        write(&root, "Runbooks/notes.txt", "not markdown");
+        write(&root, ".hidden.md", "skipped");
+
+        let pages = read_directory(&root).expect("reading the directory should work");
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(pages.len(), 2);
+        let runbooks = &pages[0];
+        assert_eq!(runbooks.path, "Runbooks");
+        assert_eq!(runbooks.title, "Run Books");
+        assert_eq!(runbooks.kind, PageKind::Collection);
+        let children: Vec<&str> = runbooks.children.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(children, vec!["Rolling back", "Deploying to production"]);
+        assert_eq!(runbooks.children[0].content.as_deref(), Some("Undo.\n"));
+        assert_eq!(runbooks.children[1].content.as_deref(), Some("Steps.\n"));
+
+        let welcome = &pages[1];
+        assert_eq!(welcome.title, "Welcome");
+        assert_eq!(welcome.content.as_deref(), Some("Hello there.\n"));
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let root = std::env::temp_dir().join(format!("nuclino-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&root).expect("creating a temp dir should work");
+        let empty = Manifest::load(&root).expect("a missing manifest is empty");
+        assert_eq!(empty.entries().count(), 0);
+
+        let mut manifest = Manifest::default();
+        let entry = ManifestEntry {
+            id: Uuid::from_u128(7),
+            kind: PageKind::Item,
+            hash: None,
+            modified: Some("2021-12-15T17:02:53.487Z".to_string()),
+        };
+        manifest.insert("Runbooks/Deploy.md", entry.clone());
+        manifest.save(&root).expect("saving should work");
+        let loaded = Manifest::load(&root).expect("loading should work");
+        std::fs::remove_dir_all(&root).ok();
+        assert_eq!(loaded.get("Runbooks/Deploy.md"), Some(&entry));
+    }
+
+    #[test]
+    fn recreating_deleted_pages() {
+        let root = std::env::temp_dir().join(format!("nuclino-reimport-{}", std::process::id()));
+        write(&root, "Welcome.md", "Hello there.\n");
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            "Welcome.md",
+            ManifestEntry {
+                id: id(1),
+                kind: PageKind::Item,
+                hash: None,
+                modified: None,
+            },
+        );
+        manifest.save(&root).expect("saving should work");
+
+        let (url, server) = serve(vec![
+            not_found(),
+            (
+                200,
+                json!({ "status": "success", "data": item(id(2), "Welcome", Some("Hello there.\n")) }),
+            ),
+        ]);
+        let imported = local_client(&url)
+            .import_directory(&root, &Uuid::parse_str(WORKSPACE_ID).expect("a valid uuid"))
+            .expect("a deleted page should be created again");
+        let manifest = Manifest::load(&root).expect("loading should work");
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].id, id(2));
+        assert_eq!(imported[0].action, ImportAction::Created);
+        assert_eq!(
+            manifest.get("Welcome.md").map(|entry| entry.id),
+            Some(id(2))
+        );
+        let requests = server.join().expect("the server should finish");
+        assert!(requests[0].starts_with(&format!("PUT /v0/items/{}", id(1))));
+        assert!(requests[1].starts_with("POST /v0/items "));
+    }
+}
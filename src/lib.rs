@@ -16,27 +16,85 @@
 #![deny(future_incompatible, clippy::unwrap_used)]
 #![warn(rust_2018_idioms, trivial_casts, missing_docs)]
 
+#[cfg(feature = "backup")]
+mod backup;
+mod book;
+mod diff;
 mod errors;
+mod export;
+mod filter;
+#[cfg(test)]
+mod fixtures;
+mod frontmatter;
+mod graph;
+mod highlight;
+mod import;
+mod linkcheck;
+mod links;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod obsidian;
+mod paths;
 mod request_types;
 mod response_types;
+mod search;
+#[cfg(feature = "search-index")]
+mod search_index;
+mod sections;
+#[cfg(feature = "site")]
+mod site;
+mod snapshot;
+mod sync;
+mod tree;
 mod types;
+mod urls;
+mod watch;
 
 use std::io::Read;
 
-use errors::make_error;
 // Our library exports.
-pub use errors::{NuclinoError, NuclinoResult};
+#[cfg(feature = "backup")]
+pub use backup::*;
+pub use book::*;
+pub use diff::*;
+use errors::make_error;
+pub use errors::{NewPageError, NuclinoError, NuclinoResult};
+pub use export::*;
+pub use filter::*;
+pub use graph::*;
+pub use highlight::*;
+pub use import::*;
+pub use linkcheck::*;
+pub use links::*;
+#[cfg(feature = "markdown")]
+pub use markdown::*;
+pub use obsidian::*;
+pub use paths::*;
 pub use request_types::*;
 use response_types::*;
+pub use search::*;
+#[cfg(feature = "search-index")]
+pub use search_index::*;
+pub use sections::*;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "site")]
+pub use site::*;
+pub use snapshot::*;
+pub use sync::*;
+pub use tree::*;
 pub use types::*;
 use urlencoding::encode;
+pub use urls::*;
 /// Re-exporting the uuid crate, because types.
 pub use uuid::Uuid;
+pub use watch::*;
 
 /// The base url for the entire API.
 pub static BASE_URL: &str = "https://api.nuclino.com";
 
+/// The largest page of results the Nuclino API will return.
+pub const PAGE_LIMIT: u8 = 100;
+
 /// The env var we check for the api key.
 pub static APIKEY_ENV_VAR: &str = "NUCLINO_API_KEY";
 
@@ -108,6 +166,21 @@ impl Client {
         Ok(result.as_vec())
     }
 
+    /// Fetch every team this client can see, following pagination until the server runs
+    /// out of results.
+    pub fn all_teams(&self) -> NuclinoResult<Vec<Team>> {
+        let mut teams: Vec<Team> = Vec::new();
+        loop {
+            let after = teams.last().map(|team| team.id().to_string());
+            let batch = self.team_list(Some(PAGE_LIMIT), after.as_deref())?;
+            let full = batch.len() == PAGE_LIMIT as usize;
+            teams.extend(batch);
+            if !full {
+                return Ok(teams);
+            }
+        }
+    }
+
     /// Fetch a single team by id.
     pub fn team(&self, id: &str) -> NuclinoResult<Team> {
         self.get(format!("{}/v0/teams/{id}", self.baseurl))
@@ -140,6 +213,21 @@ impl Client {
         Ok(result.as_vec())
     }
 
+    /// Fetch every workspace this client can see, following pagination until the server
+    /// runs out of results.
+    pub fn all_workspaces(&self) -> NuclinoResult<Vec<Workspace>> {
+        let mut workspaces: Vec<Workspace> = Vec::new();
+        loop {
+            let after = workspaces.last().map(|space| space.id().to_string());
+            let batch = self.workspace_list(Some(PAGE_LIMIT as usize), after.as_deref())?;
+            let full = batch.len() == PAGE_LIMIT as usize;
+            workspaces.extend(batch);
+            if !full {
+                return Ok(workspaces);
+            }
+        }
+    }
+
     /// Fetch a single workspace by id.
     pub fn workspace(&self, id: &Uuid) -> NuclinoResult<Workspace> {
         self.get::<Workspace>(format!("{}/v0/workspaces/{id}", self.baseurl))
@@ -160,6 +248,24 @@ impl Client {
         self.put::<Page>(format!("{}/v0/items/{id}", self.baseurl), updated)
     }
 
+    /// Update an item or collection, but only if it still meets `expected`. The page is
+    /// re-read right before writing; if it has changed, nothing is written and the
+    /// result is `NuclinoError::Conflict` holding the page as it is now. This narrows the
+    /// window for lost updates to the time between the read and the write, but can't
+    /// close it entirely, because the Nuclino API has no conditional writes.
+    pub fn page_update_if_unmodified(
+        &self,
+        id: &Uuid,
+        expected: &Precondition,
+        updated: &ModifyItem,
+    ) -> NuclinoResult<Page> {
+        let current = self.page(id)?;
+        if !expected.holds_for(&current) {
+            return Err(NuclinoError::Conflict(Box::new(current)));
+        }
+        self.page_update(id, updated)
+    }
+
     /// Delete an item or collection by id.
     pub fn page_delete(&self, id: &Uuid) -> NuclinoResult<IdOnly> {
         self.delete::<IdOnly>(format!("{}/v0/items/{id}", self.baseurl))
@@ -182,7 +288,7 @@ impl Client {
             query.push(format!("&limit={lim}"));
         }
         if let Some(id) = after {
-            query.push(format!("&limit={id}"))
+            query.push(format!("&after={id}"));
         }
         let url = format!("{}/v0/items{}", self.baseurl, query.join(""));
         self.get::<List<Page>>(url)
@@ -204,12 +310,30 @@ impl Client {
             query.push(format!("&limit={lim}"));
         }
         if let Some(id) = after {
-            query.push(format!("&limit={id}"))
+            query.push(format!("&after={id}"));
         }
         let url = format!("{}/v0/items{}", self.baseurl, query.join(""));
         self.get::<List<Page>>(url)
     }
 
+    /// Get every item and collection in a workspace, _without_ page content, following
+    /// pagination until the server runs out of results.
+    pub fn workspace_pages(&self, workspace: &Uuid) -> NuclinoResult<Vec<Page>> {
+        let mut pages: Vec<Page> = Vec::new();
+        let mut after: Option<Uuid> = None;
+        loop {
+            let batch = self
+                .all_pages_for_workspace(workspace, Some(PAGE_LIMIT), after.as_ref())?
+                .as_vec();
+            let full = batch.len() == PAGE_LIMIT as usize;
+            after = batch.last().map(|page| *page.id());
+            pages.extend(batch);
+            if !full || after.is_none() {
+                return Ok(pages);
+            }
+        }
+    }
+
     /// Search a team's pages for the given text. Returns a list of pages without content.
     /// Pass `limit` to restrict the number of results returned; the default number returned
     /// by the server is 100.
@@ -277,6 +401,21 @@ impl Client {
         Ok(bytes)
     }
 
+    /// Fetch the metadata and contents of every file attached to an item. Download
+    /// links expire after ten minutes, so this fetches fresh metadata for each file
+    /// right before downloading it.
+    pub fn download_attachments(&self, item: &Item) -> NuclinoResult<Vec<(File, Vec<u8>)>> {
+        item.content_meta()
+            .file_ids
+            .iter()
+            .map(|id| {
+                let info = self.file(id)?;
+                let bytes = self.download_file(info.download_info().url.as_str())?;
+                Ok((info, bytes))
+            })
+            .collect()
+    }
+
     /// Response processing common to all ureq http method wrappers.
     /// This function consumes the ureq Response data.
     fn process_response<T>(&self, response: ureq::Response) -> NuclinoResult<T>
@@ -291,6 +430,12 @@ impl Client {
             } else {
                 Err(NuclinoError::NoDataReturned)
             }
+        } else if body.is_server_error() {
+            // A failure body on a successful http status; the body says whose fault it is.
+            Err(NuclinoError::ServerError {
+                status,
+                message: body.message(),
+            })
         } else {
             Err(make_error(status, body.message()))
         }
@@ -331,17 +476,7 @@ impl Client {
             .post(path.as_str())
             .set("Authorization", &self.apikey)
             .send_json(payload)?;
-        let status = response.status();
-        let body: Response<T> = response.into_json()?;
-        if body.is_success() {
-            if let Some(data) = body.data() {
-                Ok(data.clone())
-            } else {
-                Err(NuclinoError::NoDataReturned)
-            }
-        } else {
-            Err(make_error(status, body.message()))
-        }
+        self.process_response(response)
     }
 
     fn delete<T>(&self, path: String) -> NuclinoResult<T>
@@ -353,16 +488,134 @@ impl Client {
             .delete(path.as_str())
             .set("Authorization", &self.apikey)
             .call()?;
-        let status = response.status();
-        let body: Response<T> = response.into_json()?;
-        if body.is_success() {
-            if let Some(data) = body.data() {
-                Ok(data.clone())
-            } else {
-                Err(NuclinoError::NoDataReturned)
-            }
-        } else {
-            Err(make_error(status, body.message()))
-        }
+        self.process_response(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    fn listing<T: Serialize>(results: &[T]) -> (u16, serde_json::Value) {
+        (
+            200,
+            json!({ "status": "success", "data": { "object": "list", "results": results } }),
+        )
+    }
+
+    #[test]
+    fn pagination_sends_after() {
+        let first: Vec<Page> = (1..=100).map(|n| item(id(n), "Page", None)).collect();
+        let (url, server) = serve(vec![
+            listing(&first),
+            listing(&[item(id(101), "Last", None)]),
+            listing::<Page>(&[]),
+        ]);
+        let client = local_client(&url);
+        let workspace = Uuid::parse_str(WORKSPACE_ID).expect("fixture id should parse");
+        let pages = client
+            .workspace_pages(&workspace)
+            .expect("listing should succeed");
+        assert_eq!(pages.len(), 101);
+        client
+            .all_pages_for_team(&id(7), Some(5), Some(&id(3)))
+            .expect("listing should succeed");
+
+        let requests = server.join().expect("the server should finish");
+        assert_eq!(
+            requests,
+            vec![
+                format!("GET /v0/items?workspaceId={WORKSPACE_ID}&limit=100 HTTP/1.1"),
+                format!(
+                    "GET /v0/items?workspaceId={WORKSPACE_ID}&limit=100&after={} HTTP/1.1",
+                    id(100)
+                ),
+                format!(
+                    "GET /v0/items?teamId={}&limit=5&after={} HTTP/1.1",
+                    id(7),
+                    id(3)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn listing_every_team() {
+        let team = |n: u128| {
+            json!({
+                "object": "team",
+                "id": id(n),
+                "url": format!("https://app.nuclino.com/team{n}"),
+                "name": format!("Team {n}"),
+                "createdAt": "2021-12-15T15:54:23.598Z",
+                "createdUserId": USER_ID,
+            })
+        };
+        let first: Vec<serde_json::Value> = (1..=100).map(team).collect();
+        let (url, server) = serve(vec![listing(&first), listing(&[team(101)])]);
+        let teams = local_client(&url)
+            .all_teams()
+            .expect("listing should succeed");
+        assert_eq!(teams.len(), 101);
+        assert_eq!(teams[100].name(), "Team 101");
+
+        let requests = server.join().expect("the server should finish");
+        assert_eq!(
+            requests,
+            vec![
+                "GET /v0/teams?limit=100 HTTP/1.1".to_string(),
+                format!("GET /v0/teams?limit=100&after={} HTTP/1.1", id(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn conditional_updates_detect_conflicts() {
+        let old = "2021-12-15T17:02:53.487Z";
+        let seen = item_full(id(1), "Status", Some("all good"), old, &[], &[]);
+        let newer = item_full(
+            id(1),
+            "Status",
+            Some("all good"),
+            "2022-01-01T00:00:00.000Z",
+            &[],
+            &[],
+        );
+        let rewritten = item_full(id(1), "Status", Some("all bad"), old, &[], &[]);
+        let (url, server) = serve(vec![
+            (200, json!({ "status": "success", "data": newer })),
+            (200, json!({ "status": "success", "data": rewritten })),
+        ]);
+        let client = local_client(&url);
+        let changes = ModifyItem {
+            title: None,
+            content: Some("all fine".to_string()),
+        };
+
+        let result =
+            client.page_update_if_unmodified(&id(1), &Precondition::unmodified(&seen), &changes);
+        let Err(NuclinoError::Conflict(current)) = result else {
+            panic!("a newer page should conflict: {result:?}");
+        };
+        assert_eq!(current.modified(), "2022-01-01T00:00:00.000Z");
+
+        let result =
+            client.page_update_if_unmodified(&id(1), &Precondition::content_of(&seen), &changes);
+        let Err(NuclinoError::Conflict(current)) = result else {
+            panic!("changed content should conflict: {result:?}");
+        };
+        assert!(
+            matches!(*current, Page::Item(ref item) if item.content().map(String::as_str) == Some("all bad"))
+        );
+
+        // Only the two reads were made; nothing was written.
+        let requests = server.join().expect("the server should finish");
+        assert_eq!(
+            requests,
+            vec![format!("GET /v0/items/{} HTTP/1.1", id(1)); 2]
+        );
     }
 }
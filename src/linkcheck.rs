@@ -0,0 +1,437 @@
+//! Checking pages for references to pages and files that no longer exist, and listing
+//! the external links in their content. The report serializes to json, and
+//! `LinkReport::is_clean()` is there for failing a CI job.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use serde::Serialize;
+use url::Url;
+use uuid::Uuid;
+
+use crate::links::{is_nuclino_url, url_spans};
+use crate::{find_links, Client, LinkKind, NuclinoResult, Page};
+
+/// Options for `Client::check_links()`.
+#[derive(Debug, Clone, Default)]
+pub struct LinkCheckOptions {
+    /// If set, every external link is checked against this list, and links not on it
+    /// make the report unclean. Entries containing `://` are url prefixes, like
+    /// `https://github.com/ceejbot/`. Other entries are host names: `example.com`
+    /// matches only that host, and `*.example.com` matches any subdomain of it.
+    pub allowlist: Option<Vec<String>>,
+}
+
+/// Where something is in a page's content. Lines and columns count from 1; columns
+/// count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+    /// The byte offset into the content.
+    pub offset: usize,
+    /// The line number.
+    pub line: usize,
+    /// The column number.
+    pub column: usize,
+}
+
+impl Location {
+    /// Find the line and column of a byte offset in some content.
+    pub fn in_content(content: &str, offset: usize) -> Self {
+        let before = &content[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Location {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// What a broken reference pointed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceKind {
+    /// An item or collection.
+    Page,
+    /// An attached file.
+    File,
+}
+
+/// A reference in a page's content metadata that no longer resolves.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenReference {
+    /// The page containing the reference.
+    pub page: Uuid,
+    /// That page's title.
+    pub title: String,
+    /// Whether a page or a file is missing.
+    pub kind: ReferenceKind,
+    /// The id that didn't resolve.
+    pub target: Uuid,
+    /// Where the reference appears in the content. Empty if the metadata lists an id
+    /// that can't be found in the text.
+    pub locations: Vec<Location>,
+}
+
+/// An `http` or `https` link to somewhere other than Nuclino.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalLink {
+    /// The page containing the link.
+    pub page: Uuid,
+    /// That page's title.
+    pub title: String,
+    /// The url.
+    pub url: String,
+    /// Where the link is in the content.
+    pub location: Location,
+    /// Whether the allowlist permits this link, or `None` if there was no allowlist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed: Option<bool>,
+}
+
+/// The results of checking a set of pages.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkReport {
+    /// How many pages were checked.
+    pub pages_checked: usize,
+    /// References to pages and files that don't exist.
+    pub broken: Vec<BrokenReference>,
+    /// Every external link found.
+    pub external: Vec<ExternalLink>,
+}
+
+impl LinkReport {
+    /// True if nothing is broken and no external link was rejected by the allowlist.
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty() && self.disallowed().next().is_none()
+    }
+
+    /// External links the allowlist rejected.
+    pub fn disallowed(&self) -> impl Iterator<Item = &ExternalLink> {
+        self.external
+            .iter()
+            .filter(|link| link.allowed == Some(false))
+    }
+}
+
+impl Display for LinkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for broken in &self.broken {
+            let kind = match broken.kind {
+                ReferenceKind::Page => "page",
+                ReferenceKind::File => "file",
+            };
+            let at: Vec<String> = broken.locations.iter().map(|l| l.to_string()).collect();
+            let at = if at.is_empty() {
+                "in metadata only".to_string()
+            } else {
+                format!("at {}", at.join(", "))
+            };
+            writeln!(
+                f,
+                "{} ({}): missing {kind} {} {at}",
+                broken.title, broken.page, broken.target
+            )?;
+        }
+        for link in self.disallowed() {
+            writeln!(
+                f,
+                "{} ({}): link not allowed at {}: {}",
+                link.title, link.page, link.location, link.url
+            )?;
+        }
+        write!(
+            f,
+            "{} pages checked: {} broken references, {} external links, {} not allowed",
+            self.pages_checked,
+            self.broken.len(),
+            self.external.len(),
+            self.disallowed().count()
+        )
+    }
+}
+
+/// Whether an allowlist permits a url. See `LinkCheckOptions::allowlist` for the
+/// format of its entries.
+pub fn is_allowed(url: &str, allowlist: &[String]) -> bool {
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(str::to_ascii_lowercase));
+    allowlist.iter().any(|entry| {
+        let entry = entry.trim().to_ascii_lowercase();
+        if entry.contains("://") {
+            return url.to_ascii_lowercase().starts_with(&entry);
+        }
+        let Some(host) = host.as_deref() else {
+            return false;
+        };
+        match entry.strip_prefix("*.") {
+            Some(domain) => host.ends_with(&format!(".{domain}")),
+            None => host == entry,
+        }
+    })
+}
+
+/// Check pages, asking `page_exists` and `file_exists` about ids not among the pages
+/// themselves. Each id is asked about once.
+fn check_pages<P, F>(
+    pages: &[Page],
+    options: &LinkCheckOptions,
+    mut page_exists: P,
+    mut file_exists: F,
+) -> NuclinoResult<LinkReport>
+where
+    P: FnMut(&Uuid) -> NuclinoResult<bool>,
+    F: FnMut(&Uuid) -> NuclinoResult<bool>,
+{
+    let known: HashSet<Uuid> = pages.iter().map(|page| *page.id()).collect();
+    let mut pages_seen: HashMap<Uuid, bool> = HashMap::new();
+    let mut files_seen: HashMap<Uuid, bool> = HashMap::new();
+    let mut report = LinkReport {
+        pages_checked: pages.len(),
+        ..LinkReport::default()
+    };
+
+    for page in pages {
+        let Page::Item(item) = page else {
+            continue;
+        };
+        let content = item.content().map(String::as_str).unwrap_or_default();
+        let links = find_links(content);
+        let locations = |target: &Uuid, kind: ReferenceKind| -> Vec<Location> {
+            links
+                .iter()
+                .filter(|link| {
+                    link.id == *target
+                        && (link.kind == LinkKind::File) == (kind == ReferenceKind::File)
+                })
+                .map(|link| Location::in_content(content, link.span.start))
+                .collect()
+        };
+
+        let meta = item.content_meta();
+        for target in &meta.item_ids {
+            if known.contains(target) {
+                continue;
+            }
+            let exists = match pages_seen.get(target) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = page_exists(target)?;
+                    pages_seen.insert(*target, exists);
+                    exists
+                }
+            };
+            if !exists {
+                report.broken.push(BrokenReference {
+                    page: *item.id(),
+                    title: item.title().to_string(),
+                    kind: ReferenceKind::Page,
+                    target: *target,
+                    locations: locations(target, ReferenceKind::Page),
+                });
+            }
+        }
+        for target in &meta.file_ids {
+            let exists = match files_seen.get(target) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = file_exists(target)?;
+                    files_seen.insert(*target, exists);
+                    exists
+                }
+            };
+            if !exists {
+                report.broken.push(BrokenReference {
+                    page: *item.id(),
+                    title: item.title().to_string(),
+                    kind: ReferenceKind::File,
+                    target: *target,
+                    locations: locations(target, ReferenceKind::File),
+                });
+            }
+        }
+
+        for span in url_spans(content) {
+            let url = &content[span.clone()];
+            if is_nuclino_url(url) {
+                continue;
+            }
+            report.external.push(ExternalLink {
+                page: *item.id(),
+                title: item.title().to_string(),
+                url: url.to_string(),
+                location: Location::in_content(content, span.start),
+                allowed: options
+                    .allowlist
+                    .as_ref()
+                    .map(|allowlist| is_allowed(url, allowlist)),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Turn a lookup into whether the thing exists: a 404 means it doesn't, and any other
+/// error is passed along.
+fn exists<T>(result: NuclinoResult<T>) -> NuclinoResult<bool> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(e) if e.is_not_found() => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+impl Client {
+    /// Check a workspace's hydrated pages for references to pages and files that no
+    /// longer exist, and list their external links. Pages that aren't among `pages` are
+    /// looked up, one request per distinct id; a 404 response means the page or file is
+    /// gone. Pages in the trash still resolve, so links to them aren't reported.
+    pub fn check_links(
+        &self,
+        pages: &[Page],
+        options: &LinkCheckOptions,
+    ) -> NuclinoResult<LinkReport> {
+        check_pages(
+            pages,
+            options,
+            |id| exists(self.page(id)),
+            |id| exists(self.file(id)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+    use crate::NuclinoError;
+
+    fn pages() -> Vec<Page> {
+        let content = format!(
+            "# Links\n\nSee [gone](https://app.nuclino.com/t/b/{gone}) and [here](https://app.nuclino.com/t/b/{here}).\n\
+             ![lost](https://files.nuclino.com/files/{lost}/lost.png)\n\
+             Docs at https://docs.rs/nuclino-rs, code at <https://github.com/ceejbot/nuclino-rs>.\n\
+             `https://ignored.example.com`\n",
+            gone = id(90),
+            here = id(2),
+            lost = id(91),
+        );
+        let mut with_files: serde_json::Value = serde_json::to_value(item_full(
+            id(1),
+            "Index",
+            Some(&content),
+            "2021-12-15T17:02:53.487Z",
+            &[id(90), id(2), id(92)],
+            &[],
+        ))
+        .expect("fixture should serialize");
+        with_files["contentMeta"]["fileIds"] = serde_json::json!([id(91), id(93)]);
+        vec![
+            serde_json::from_value(with_files).expect("fixture should deserialize"),
+            item(id(2), "Here", Some("Nothing to see.")),
+        ]
+    }
+
+    fn check(options: &LinkCheckOptions) -> LinkReport {
+        let mut asked = Vec::new();
+        let report = check_pages(
+            &pages(),
+            options,
+            |page| {
+                asked.push(*page);
+                Ok(*page == id(92))
+            },
+            |file| Ok(*file == id(93)),
+        )
+        .expect("checking should succeed");
+        // Pages in the set aren't looked up.
+        assert_eq!(asked, vec![id(90), id(92)]);
+        report
+    }
+
+    #[test]
+    fn broken_references() {
+        let report = check(&LinkCheckOptions::default());
+        assert_eq!(report.pages_checked, 2);
+        let broken: Vec<(ReferenceKind, Uuid, usize)> = report
+            .broken
+            .iter()
+            .map(|b| (b.kind, b.target, b.locations.len()))
+            .collect();
+        assert_eq!(
+            broken,
+            vec![
+                (ReferenceKind::Page, id(90), 1),
+                (ReferenceKind::File, id(91), 1)
+            ]
+        );
+        assert_eq!(report.broken[0].locations[0].line, 3);
+        assert_eq!(report.broken[0].locations[0].column, 5);
+        assert_eq!(report.broken[1].locations[0].line, 4);
+        assert!(!report.is_clean());
+        let json = serde_json::to_value(&report).expect("the report should serialize");
+        assert_eq!(json["broken"][1]["kind"], "file");
+        assert!(report
+            .to_string()
+            .contains("Index (00000000-0000-0000-0000-000000000001): missing page"));
+    }
+
+    #[test]
+    fn external_links() {
+        let report = check(&LinkCheckOptions::default());
+        let urls: Vec<&str> = report.external.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://docs.rs/nuclino-rs",
+                "https://github.com/ceejbot/nuclino-rs"
+            ]
+        );
+        assert!(report.external.iter().all(|l| l.allowed.is_none()));
+
+        let options = LinkCheckOptions {
+            allowlist: Some(vec![
+                "*.rs".to_string(),
+                "https://github.com/ceejbot/".to_string(),
+            ]),
+        };
+        let report = check(&options);
+        assert!(report.external.iter().all(|l| l.allowed == Some(true)));
+
+        let options = LinkCheckOptions {
+            allowlist: Some(vec!["docs.rs".to_string()]),
+        };
+        let report = check(&options);
+        let rejected: Vec<&str> = report.disallowed().map(|l| l.url.as_str()).collect();
+        assert_eq!(rejected, vec!["https://github.com/ceejbot/nuclino-rs"]);
+    }
+
+    #[test]
+    fn not_found_errors() {
+        let missing = NuclinoError::ClientError {
+            status: 404,
+            message: "not found".to_string(),
+        };
+        assert!(!exists::<()>(Err(missing)).expect("a 404 isn't an error here"));
+        let denied = NuclinoError::ClientError {
+            status: 403,
+            message: "forbidden".to_string(),
+        };
+        assert!(exists::<()>(Err(denied)).is_err());
+        assert!(is_allowed(
+            "https://a.b.example.com/x",
+            &["*.example.com".to_string()]
+        ));
+        assert!(!is_allowed(
+            "https://example.com/x",
+            &["*.example.com".to_string()]
+        ));
+    }
+}
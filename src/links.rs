@@ -0,0 +1,406 @@
+//! Finding the links to other pages and the embedded files in item content, with the
+//! byte ranges where they appear, and rewriting them.
+//!
+//! Nuclino writes three kinds of references into Markdown:
+//!
+//! - item links: `[Some text](https://app.nuclino.com/t/b/<page-id>)`
+//! - mentions, which the editor shows as the page's title in a chip, and which are bare
+//!   page urls, optionally in angle brackets: `<https://app.nuclino.com/t/b/<page-id>>`
+//! - file embeds and attachments: `![name](https://files.nuclino.com/files/<file-id>/name.png)`,
+//!   or the same without the `!` for non-image files.
+//!
+//! Anything inside code spans or fenced code blocks is ignored.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use url::Url;
+use uuid::Uuid;
+
+use crate::{parse_url, Item, NuclinoUrl};
+
+/// The kinds of reference to other Nuclino objects found in content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// A Markdown link to a page.
+    Item,
+    /// A bare page url, which Nuclino shows as a mention of the page.
+    Mention,
+    /// A link to or embed of a file attached to the item.
+    File,
+}
+
+/// One reference found in content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentLink {
+    /// What kind of reference this is.
+    pub kind: LinkKind,
+    /// The id of the page or file referred to.
+    pub id: Uuid,
+    /// The link text, for Markdown links. For images this is the alt text.
+    pub text: Option<String>,
+    /// The whole construct, from `[` or `![` to the closing `)` for Markdown links, or
+    /// the url with any angle brackets around it for mentions.
+    pub span: Range<usize>,
+    /// Just the url.
+    pub url_span: Range<usize>,
+    /// Just the id inside the url.
+    pub id_span: Range<usize>,
+}
+
+impl ContentLink {
+    /// The sentence the link appears in, for showing where links are used. Sentences end
+    /// at line breaks or at `.`, `!`, or `?` followed by whitespace.
+    pub fn sentence<'a>(&self, content: &'a str) -> &'a str {
+        let before = &content[..self.span.start];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(i, c)| {
+                *c == '\n'
+                    || (matches!(c, '.' | '!' | '?')
+                        && before[i + 1..].starts_with(char::is_whitespace))
+            })
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(0);
+        let after = &content[self.span.end..];
+        let end = after
+            .char_indices()
+            .find(|(i, c)| {
+                *c == '\n'
+                    || (matches!(c, '.' | '!' | '?')
+                        && after[i + 1..]
+                            .chars()
+                            .next()
+                            .map_or(true, char::is_whitespace))
+            })
+            .map(|(i, c)| if c == '\n' { i } else { i + 1 })
+            .unwrap_or(after.len());
+        content[start..self.span.end + end].trim()
+    }
+}
+
+/// Find every page link, mention, and file reference in some Markdown, in order.
+pub fn find_links(content: &str) -> Vec<ContentLink> {
+    let mut links = Vec::new();
+    for Range { start, end } in url_spans(content) {
+        let url = &content[start..end];
+        let Some((kind, id)) = classify(url) else {
+            continue;
+        };
+        let Some(id_offset) = url.to_ascii_lowercase().find(&id.to_string()) else {
+            continue;
+        };
+        let id_span = start + id_offset..start + id_offset + 36;
+
+        let before = &content[..start];
+        let (kind, text, span) = if before.ends_with("](") {
+            match markdown_link(content, start, end) {
+                Some((text, span)) => {
+                    let kind = if kind == LinkKind::Mention {
+                        LinkKind::Item
+                    } else {
+                        kind
+                    };
+                    (kind, Some(text), span)
+                }
+                None => continue,
+            }
+        } else if before.ends_with('<') && content[end..].starts_with('>') {
+            (kind, None, start - 1..end + 1)
+        } else {
+            (kind, None, start..end)
+        };
+        links.push(ContentLink {
+            kind,
+            id,
+            text,
+            span,
+            url_span: start..end,
+            id_span,
+        });
+    }
+    links
+}
+
+/// Replace the ids in page links, mentions, and file references according to `ids`,
+/// leaving everything else alone. Ids not in the map aren't touched.
+pub fn remap_ids(content: &str, ids: &HashMap<Uuid, Uuid>) -> String {
+    rewrite_links(content, |link| {
+        let new = ids.get(&link.id)?;
+        let url = &content[link.url_span.clone()];
+        let at = link.id_span.start - link.url_span.start;
+        Some(format!("{}{}{}", &url[..at], new, &url[at + 36..]))
+    })
+}
+
+/// Rewrite the urls of links in some content. `rewrite` is called for each link and
+/// returns its new url, or `None` to leave it as it is.
+pub fn rewrite_links<F>(content: &str, mut rewrite: F) -> String
+where
+    F: FnMut(&ContentLink) -> Option<String>,
+{
+    let mut rewritten = String::with_capacity(content.len());
+    let mut copied = 0;
+    for link in find_links(content) {
+        if let Some(url) = rewrite(&link) {
+            rewritten.push_str(&content[copied..link.url_span.start]);
+            rewritten.push_str(&url);
+            copied = link.url_span.end;
+        }
+    }
+    rewritten.push_str(&content[copied..]);
+    rewritten
+}
+
+impl Item {
+    /// The page links, mentions, and file references in this item's content.
+    pub fn links(&self) -> Vec<ContentLink> {
+        self.content().map(|c| find_links(c)).unwrap_or_default()
+    }
+}
+
+/// The byte ranges of every `http` and `https` url in some Markdown, outside of code.
+pub(crate) fn url_spans(content: &str) -> Vec<Range<usize>> {
+    let code = code_ranges(content);
+    let in_code = |at: usize| code.iter().any(|range| range.contains(&at));
+    let mut spans = Vec::new();
+    let mut search_from = 0;
+    while let Some(found) = next_url_start(&content[search_from..]) {
+        let start = search_from + found;
+        let end = url_end(content, start);
+        search_from = end.max(start + 1);
+        if !in_code(start) && end > start {
+            spans.push(start..end);
+        }
+    }
+    spans
+}
+
+/// Whether a url points at Nuclino itself: the app, the api, or file storage.
+pub(crate) fn is_nuclino_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(str::to_string))
+        .map_or(false, |host| {
+            host == "nuclino.com" || host.ends_with(".nuclino.com")
+        })
+}
+
+fn next_url_start(text: &str) -> Option<usize> {
+    let http = text.find("http://");
+    let https = text.find("https://");
+    match (http, https) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Where a url starting at `start` ends: at whitespace, a closing bracket of the
+/// Markdown around it, or trailing punctuation.
+fn url_end(content: &str, start: usize) -> usize {
+    let rest = &content[start..];
+    let mut end = rest
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | ')' | ']' | '`'))
+        .unwrap_or(rest.len());
+    while end > 0 && rest[..end].ends_with(['.', ',', ';', ':', '!', '?', '\'']) {
+        end -= 1;
+    }
+    start + end
+}
+
+/// Decide whether a url refers to a Nuclino page or file.
+fn classify(url: &str) -> Option<(LinkKind, Uuid)> {
+    if let Ok(NuclinoUrl::Page(id)) = parse_url(url) {
+        return Some((LinkKind::Mention, id));
+    }
+    if !is_nuclino_url(url) {
+        return None;
+    }
+    let parsed = Url::parse(url).ok()?;
+    let mut segments = parsed.path_segments()?;
+    if segments.next() != Some("files") {
+        return None;
+    }
+    let id = Uuid::try_parse(segments.next()?).ok()?;
+    Some((LinkKind::File, id))
+}
+
+/// For a url that's the destination of a Markdown link, find the link text and the
+/// span of the whole link.
+fn markdown_link(
+    content: &str,
+    url_start: usize,
+    url_end: usize,
+) -> Option<(String, Range<usize>)> {
+    // The destination may have a title after the url; the link ends at the next `)`.
+    let close = url_end + content[url_end..].find(')')?;
+    let text_end = url_start - 2;
+    let bytes = content.as_bytes();
+    let mut depth = 0usize;
+    let mut open = None;
+    for i in (0..text_end).rev() {
+        match bytes[i] {
+            b']' if i == 0 || bytes[i - 1] != b'\\' => depth += 1,
+            b'[' if i == 0 || bytes[i - 1] != b'\\' => {
+                if depth == 0 {
+                    open = Some(i);
+                    break;
+                }
+                depth -= 1;
+            }
+            b'\n' if i > 0 && bytes[i - 1] == b'\n' => return None,
+            _ => {}
+        }
+    }
+    let open = open?;
+    let start = if open > 0 && bytes[open - 1] == b'!' {
+        open - 1
+    } else {
+        open
+    };
+    Some((content[open + 1..text_end].to_string(), start..close + 1))
+}
+
+/// The byte ranges of fenced code blocks and inline code spans.
+fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start_matches(' ');
+        let marker = trimmed.chars().next().unwrap_or(' ');
+        let run = trimmed.chars().take_while(|c| *c == marker).count();
+        if (marker == '`' || marker == '~') && run >= 3 {
+            match fence {
+                None => {
+                    fence = Some((marker, run, start));
+                    continue;
+                }
+                Some((open, len, fence_start)) if open == marker && run >= len => {
+                    ranges.push(fence_start..offset);
+                    fence = None;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        if fence.is_none() {
+            ranges.extend(code_spans(line, start));
+        }
+    }
+    if let Some((_, _, fence_start)) = fence {
+        ranges.push(fence_start..content.len());
+    }
+    ranges
+}
+
+/// Inline code spans in one line: a run of backticks up to the next run of the same
+/// length.
+fn code_spans(line: &str, offset: usize) -> Vec<Range<usize>> {
+    let bytes = line.as_bytes();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let run = bytes[i..].iter().take_while(|b| **b == b'`').count();
+        let ticks = "`".repeat(run);
+        let after = i + run;
+        match line[after..].find(&ticks) {
+            Some(close) => {
+                let end = after + close + run;
+                spans.push(offset + i..offset + end);
+                i = end;
+            }
+            None => i = after,
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::id;
+
+    fn sample() -> String {
+        format!(
+            "# Links\n\nSee [the [best] runbook](https://app.nuclino.com/t/b/{one} \"Runbook\") first. \
+             Ask <https://app.nuclino.com/t/b/{two}> or https://app.nuclino.com/Team/Ops/Deploy-{three}.\n\n\
+             ![diagram](https://files.nuclino.com/files/{four}/diagram.png)\n\n\
+             `https://app.nuclino.com/t/b/{five}` is code.\n\n```\nhttps://app.nuclino.com/t/b/{five}\n```\n\n\
+             [elsewhere](https://example.com/t/b/{five})\n",
+            one = id(1),
+            two = id(2),
+            three = id(3),
+            four = id(4),
+            five = id(5),
+        )
+    }
+
+    #[test]
+    fn finding_links() {
+        let content = sample();
+        let links = find_links(&content);
+        let summary: Vec<(LinkKind, Uuid)> = links.iter().map(|l| (l.kind, l.id)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (LinkKind::Item, id(1)),
+                (LinkKind::Mention, id(2)),
+                (LinkKind::Mention, id(3)),
+                (LinkKind::File, id(4)),
+            ]
+        );
+        let first = &links[0];
+        assert_eq!(first.text.as_deref(), Some("the [best] runbook"));
+        assert!(content[first.span.clone()].starts_with("[the [best]"));
+        assert!(content[first.span.clone()].ends_with("\"Runbook\")"));
+        assert_eq!(&content[first.id_span.clone()], id(1).to_string());
+        assert_eq!(
+            &content[links[1].span.clone()],
+            format!("<https://app.nuclino.com/t/b/{}>", id(2))
+        );
+        assert!(content[links[2].url_span.clone()].ends_with(&id(3).to_string()));
+        assert_eq!(links[3].text.as_deref(), Some("diagram"));
+        assert!(content[links[3].span.clone()].starts_with("![diagram]"));
+        assert_eq!(
+            first.sentence(&content),
+            &content[first.span.start - 4..first.span.end + 7]
+        );
+        assert!(links[1].sentence(&content).starts_with("Ask <"));
+    }
+
+    #[test]
+    fn rewriting_links() {
+        let content = sample();
+        let mut ids = HashMap::new();
+        ids.insert(id(1), id(11));
+        ids.insert(id(3), id(33));
+        ids.insert(id(5), id(55));
+        let rewritten = remap_ids(&content, &ids);
+        let expected = content
+            .replacen(&id(1).to_string(), &id(11).to_string(), 1)
+            .replacen(&id(3).to_string(), &id(33).to_string(), 1);
+        assert_eq!(rewritten, expected);
+
+        let upper = format!(
+            "[x](https://app.nuclino.com/t/b/{})",
+            id(1).to_string().to_uppercase()
+        );
+        assert_eq!(
+            remap_ids(&upper, &ids),
+            format!("[x](https://app.nuclino.com/t/b/{})", id(11))
+        );
+
+        let dropped = rewrite_links(&content, |link| {
+            (link.kind == LinkKind::File).then(|| "diagram.png".to_string())
+        });
+        assert!(dropped.contains("![diagram](diagram.png)"));
+    }
+}
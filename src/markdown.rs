@@ -0,0 +1,940 @@
+//! A Markdown syntax tree for item content, so pages can be inspected and transformed
+//! programmatically. Parsing is done by `pulldown-cmark` with the extensions Nuclino's
+//! editor produces: tables, strikethrough, and task lists. Serializing writes the same
+//! constructs back out in a consistent style: `#` headings, fenced code blocks, `*` for
+//! emphasis, `**` for strong text, and the list markers the original used. Parsing the
+//! output again gives back an identical tree.
+//!
+//! ```no_run
+//! use nuclino_rs::{Client, Inline, ModifyItem, NuclinoResult, Page, Uuid};
+//!
+//! fn upgrade_links(client: &Client, id: &Uuid) -> NuclinoResult<()> {
+//!     let Page::Item(item) = client.page(id)? else {
+//!         return Ok(());
+//!     };
+//!     let mut document = item.document();
+//!     document.for_each_inline_mut(&mut |inline| {
+//!         if let Inline::Link { url, .. } = inline {
+//!             *url = url.replace("http://", "https://");
+//!         }
+//!     });
+//!     let changes = ModifyItem {
+//!         title: None,
+//!         content: Some(document.to_markdown()),
+//!     };
+//!     client.page_update(id, &changes)?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, OffsetIter, Options, Parser, Tag};
+
+use crate::Item;
+
+/// A parsed Markdown document: a sequence of blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    /// The top-level blocks, in order.
+    pub blocks: Vec<Block>,
+}
+
+/// A block-level Markdown element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// A heading, with its level from 1 to 6.
+    Heading {
+        /// The heading level.
+        level: u8,
+        /// The heading text.
+        content: Vec<Inline>,
+    },
+    /// A paragraph of text.
+    Paragraph(Vec<Inline>),
+    /// A bulleted or numbered list.
+    List(ListBlock),
+    /// A code block. `info` is the text after the opening fence, usually a language name.
+    CodeBlock {
+        /// The info string, if there was one.
+        info: Option<String>,
+        /// The code, ending with a newline unless empty.
+        code: String,
+    },
+    /// A block quote.
+    BlockQuote(Vec<Block>),
+    /// A table.
+    Table(TableBlock),
+    /// A horizontal rule.
+    Rule,
+    /// Raw HTML, passed through untouched.
+    Html(String),
+}
+
+/// A list and its items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListBlock {
+    /// The number of the first item, for numbered lists; `None` for bulleted lists.
+    pub start: Option<u64>,
+    /// The bullet character (`-`, `*`, or `+`) or the number delimiter (`.` or `)`).
+    pub marker: char,
+    /// Whether the list is tight, with no blank lines between items.
+    pub tight: bool,
+    /// The items.
+    pub items: Vec<ListItem>,
+}
+
+/// One item in a list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListItem {
+    /// For task list items, whether the box is checked.
+    pub checked: Option<bool>,
+    /// The item's content.
+    pub blocks: Vec<Block>,
+}
+
+/// How a table column is aligned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// No alignment was given.
+    #[default]
+    None,
+    /// `:---`
+    Left,
+    /// `:---:`
+    Center,
+    /// `---:`
+    Right,
+}
+
+/// A table: a header row, alignments for each column, and body rows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableBlock {
+    /// Column alignments.
+    pub alignments: Vec<Alignment>,
+    /// The header cells.
+    pub header: Vec<Vec<Inline>>,
+    /// The body rows, each a list of cells.
+    pub rows: Vec<Vec<Vec<Inline>>>,
+}
+
+/// An inline Markdown element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    /// Plain text, with any Markdown escapes already removed.
+    Text(String),
+    /// Inline code.
+    Code(String),
+    /// Emphasized text.
+    Emphasis(Vec<Inline>),
+    /// Strong text.
+    Strong(Vec<Inline>),
+    /// Struck-through text.
+    Strikethrough(Vec<Inline>),
+    /// A link. Links to other Nuclino pages are ordinary links to their urls.
+    Link {
+        /// The link destination.
+        url: String,
+        /// The link title, which is usually empty.
+        title: String,
+        /// The link text.
+        content: Vec<Inline>,
+    },
+    /// An image.
+    Image {
+        /// The image source.
+        url: String,
+        /// The image title, which is usually empty.
+        title: String,
+        /// The alternate text.
+        alt: Vec<Inline>,
+    },
+    /// A line break that came from a newline in a paragraph.
+    SoftBreak,
+    /// An explicit line break.
+    HardBreak,
+    /// Raw inline HTML.
+    Html(String),
+}
+
+impl Document {
+    /// Parse Markdown into a document.
+    pub fn parse(markdown: &str) -> Self {
+        let mut reader = Reader {
+            source: markdown,
+            events: Parser::new_ext(markdown, parser_options())
+                .into_offset_iter()
+                .peekable(),
+            paragraphs: 0,
+        };
+        Document {
+            blocks: reader.blocks(),
+        }
+    }
+
+    /// Write the document back out as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut out = write_blocks(&self.blocks, "\n\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The document's headings, as their level and plain text.
+    pub fn headings(&self) -> Vec<(u8, String)> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Heading { level, content } => Some((*level, plain_text(content))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Call `f` on every inline element in the document, outermost first.
+    pub fn for_each_inline<F: FnMut(&Inline)>(&self, f: &mut F) {
+        for block in &self.blocks {
+            block.for_each_inline(f);
+        }
+    }
+
+    /// Call `f` on every inline element in the document, outermost first, allowing
+    /// changes. Changes to an element's children are visited afterwards.
+    pub fn for_each_inline_mut<F: FnMut(&mut Inline)>(&mut self, f: &mut F) {
+        for block in &mut self.blocks {
+            block.for_each_inline_mut(f);
+        }
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_markdown())
+    }
+}
+
+impl Block {
+    fn inline_groups(&self) -> Vec<&Vec<Inline>> {
+        match self {
+            Block::Heading { content, .. } | Block::Paragraph(content) => vec![content],
+            Block::Table(table) => table
+                .header
+                .iter()
+                .chain(table.rows.iter().flatten())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn children(&self) -> Vec<&Block> {
+        match self {
+            Block::BlockQuote(blocks) => blocks.iter().collect(),
+            Block::List(list) => list.items.iter().flat_map(|item| &item.blocks).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn for_each_inline<F: FnMut(&Inline)>(&self, f: &mut F) {
+        for group in self.inline_groups() {
+            visit(group, f);
+        }
+        for child in self.children() {
+            child.for_each_inline(f);
+        }
+    }
+
+    fn for_each_inline_mut<F: FnMut(&mut Inline)>(&mut self, f: &mut F) {
+        match self {
+            Block::Heading { content, .. } | Block::Paragraph(content) => visit_mut(content, f),
+            Block::Table(table) => {
+                for cell in table
+                    .header
+                    .iter_mut()
+                    .chain(table.rows.iter_mut().flatten())
+                {
+                    visit_mut(cell, f);
+                }
+            }
+            Block::BlockQuote(blocks) => {
+                for block in blocks {
+                    block.for_each_inline_mut(f);
+                }
+            }
+            Block::List(list) => {
+                for block in list.items.iter_mut().flat_map(|item| &mut item.blocks) {
+                    block.for_each_inline_mut(f);
+                }
+            }
+            Block::CodeBlock { .. } | Block::Rule | Block::Html(_) => {}
+        }
+    }
+}
+
+impl Inline {
+    fn children(&self) -> Option<&Vec<Inline>> {
+        match self {
+            Inline::Emphasis(children)
+            | Inline::Strong(children)
+            | Inline::Strikethrough(children) => Some(children),
+            Inline::Link { content, .. } => Some(content),
+            Inline::Image { alt, .. } => Some(alt),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<Inline>> {
+        match self {
+            Inline::Emphasis(children)
+            | Inline::Strong(children)
+            | Inline::Strikethrough(children) => Some(children),
+            Inline::Link { content, .. } => Some(content),
+            Inline::Image { alt, .. } => Some(alt),
+            _ => None,
+        }
+    }
+}
+
+fn visit<F: FnMut(&Inline)>(inlines: &[Inline], f: &mut F) {
+    for inline in inlines {
+        f(inline);
+        if let Some(children) = inline.children() {
+            visit(children, f);
+        }
+    }
+}
+
+fn visit_mut<F: FnMut(&mut Inline)>(inlines: &mut [Inline], f: &mut F) {
+    for inline in inlines {
+        f(inline);
+        if let Some(children) = inline.children_mut() {
+            visit_mut(children, f);
+        }
+    }
+}
+
+/// The text of some inline elements with all formatting dropped.
+pub fn plain_text(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(s) | Inline::Code(s) => text.push_str(s),
+            Inline::SoftBreak | Inline::HardBreak => text.push(' '),
+            Inline::Html(_) => {}
+            other => {
+                if let Some(children) = other.children() {
+                    text.push_str(&plain_text(children));
+                }
+            }
+        }
+    }
+    text
+}
+
+impl Item {
+    /// Parse this item's content as Markdown. Items without content give an empty
+    /// document.
+    pub fn document(&self) -> Document {
+        Document::parse(self.content().map(String::as_str).unwrap_or_default())
+    }
+}
+
+/// The Markdown extensions Nuclino uses.
+pub(crate) fn parser_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options
+}
+
+/// Turns pulldown-cmark's event stream into a tree.
+struct Reader<'a> {
+    source: &'a str,
+    events: Peekable<OffsetIter<'a, 'a>>,
+    /// How many explicit paragraphs have been read; list items in tight lists have none.
+    paragraphs: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Read blocks until the end of the enclosing element, consuming its end event.
+    fn blocks(&mut self) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        loop {
+            // Inline content directly inside a block is how tight list items appear.
+            if matches!(self.events.peek(), Some((event, _)) if is_inline(event)) {
+                let mut inlines = Vec::new();
+                while matches!(self.events.peek(), Some((event, _)) if is_inline(event) || matches!(event, Event::Html(_)))
+                {
+                    let Some((event, _)) = self.events.next() else {
+                        break;
+                    };
+                    if let Some(inline) = self.inline(event) {
+                        push_inline(&mut inlines, inline);
+                    }
+                }
+                blocks.push(Block::Paragraph(inlines));
+                continue;
+            }
+            let Some((event, range)) = self.events.next() else {
+                return blocks;
+            };
+            let block = match event {
+                Event::End(_) => return blocks,
+                Event::Start(Tag::Paragraph) => {
+                    self.paragraphs += 1;
+                    Block::Paragraph(self.inlines())
+                }
+                Event::Start(Tag::Heading(level, _, _)) => Block::Heading {
+                    level: level as u8,
+                    content: self.inlines(),
+                },
+                Event::Start(Tag::BlockQuote) => Block::BlockQuote(self.blocks()),
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let info = match kind {
+                        CodeBlockKind::Fenced(info) if !info.trim().is_empty() => {
+                            Some(info.trim().to_string())
+                        }
+                        _ => None,
+                    };
+                    Block::CodeBlock {
+                        info,
+                        code: self.text(),
+                    }
+                }
+                Event::Start(Tag::List(start)) => Block::List(self.list(start, range)),
+                Event::Start(Tag::Table(alignments)) => Block::Table(self.table(alignments)),
+                Event::Rule => Block::Rule,
+                Event::Html(html) => {
+                    // HTML blocks arrive a line at a time.
+                    let mut html = html.to_string();
+                    let mut end = range.end;
+                    while let Some((Event::Html(more), next)) = self.events.peek() {
+                        if next.start != end {
+                            break;
+                        }
+                        html.push_str(more);
+                        end = next.end;
+                        self.events.next();
+                    }
+                    Block::Html(html.trim_end_matches('\n').to_string())
+                }
+                // Anything else, like footnotes, isn't enabled.
+                _ => continue,
+            };
+            blocks.push(block);
+        }
+    }
+
+    fn list(&mut self, start: Option<u64>, range: Range<usize>) -> ListBlock {
+        let marker = self.source[range]
+            .trim_start()
+            .chars()
+            .find(|c| !c.is_ascii_digit())
+            .unwrap_or('-');
+        let before = self.paragraphs;
+        let mut items = Vec::new();
+        while let Some((event, _)) = self.events.next() {
+            match event {
+                Event::Start(Tag::Item) => {
+                    let mut item = ListItem::default();
+                    if let Some((Event::TaskListMarker(checked), _)) = self.events.peek() {
+                        item.checked = Some(*checked);
+                        self.events.next();
+                    }
+                    item.blocks = self.blocks();
+                    // In loose lists the marker comes inside the first paragraph.
+                    if let Some(Block::Paragraph(inlines)) = item.blocks.first_mut() {
+                        if let Some(Inline::Html(marker)) = inlines.first() {
+                            if let Some(checked) = task_marker(marker) {
+                                item.checked = Some(checked);
+                                inlines.remove(0);
+                            }
+                        }
+                    }
+                    items.push(item);
+                }
+                _ => break,
+            }
+        }
+        ListBlock {
+            start,
+            marker,
+            tight: self.paragraphs == before,
+            items,
+        }
+    }
+
+    fn table(&mut self, alignments: Vec<pulldown_cmark::Alignment>) -> TableBlock {
+        let mut table = TableBlock {
+            alignments: alignments
+                .into_iter()
+                .map(|a| match a {
+                    pulldown_cmark::Alignment::None => Alignment::None,
+                    pulldown_cmark::Alignment::Left => Alignment::Left,
+                    pulldown_cmark::Alignment::Center => Alignment::Center,
+                    pulldown_cmark::Alignment::Right => Alignment::Right,
+                })
+                .collect(),
+            ..TableBlock::default()
+        };
+        while let Some((event, _)) = self.events.next() {
+            match event {
+                Event::Start(Tag::TableHead) => table.header = self.cells(),
+                Event::Start(Tag::TableRow) => {
+                    let row = self.cells();
+                    table.rows.push(row);
+                }
+                _ => break,
+            }
+        }
+        table
+    }
+
+    fn cells(&mut self) -> Vec<Vec<Inline>> {
+        let mut cells = Vec::new();
+        while let Some((Event::Start(Tag::TableCell), _)) = self.events.next() {
+            cells.push(self.inlines());
+        }
+        cells
+    }
+
+    /// Read inline elements until the end of the enclosing element.
+    fn inlines(&mut self) -> Vec<Inline> {
+        let mut inlines = Vec::new();
+        while let Some((event, _)) = self.events.next() {
+            if let Event::End(_) = event {
+                break;
+            }
+            if let Some(inline) = self.inline(event) {
+                push_inline(&mut inlines, inline);
+            }
+        }
+        inlines
+    }
+
+    fn inline(&mut self, event: Event<'a>) -> Option<Inline> {
+        let inline = match event {
+            Event::Text(text) => Inline::Text(text.to_string()),
+            Event::Code(code) => Inline::Code(code.to_string()),
+            Event::Html(html) => Inline::Html(html.to_string()),
+            Event::SoftBreak => Inline::SoftBreak,
+            Event::HardBreak => Inline::HardBreak,
+            Event::TaskListMarker(checked) => {
+                Inline::Html(if checked { "[x]" } else { "[ ]" }.to_string())
+            }
+            Event::Start(Tag::Emphasis) => Inline::Emphasis(self.inlines()),
+            Event::Start(Tag::Strong) => Inline::Strong(self.inlines()),
+            Event::Start(Tag::Strikethrough) => Inline::Strikethrough(self.inlines()),
+            Event::Start(Tag::Link(_, url, title)) => Inline::Link {
+                url: url.to_string(),
+                title: title.to_string(),
+                content: self.inlines(),
+            },
+            Event::Start(Tag::Image(_, url, title)) => Inline::Image {
+                url: url.to_string(),
+                title: title.to_string(),
+                alt: self.inlines(),
+            },
+            _ => return None,
+        };
+        Some(inline)
+    }
+
+    /// Read the text of a code block.
+    fn text(&mut self) -> String {
+        let mut text = String::new();
+        for (event, _) in self.events.by_ref() {
+            match event {
+                Event::Text(t) => text.push_str(&t),
+                _ => break,
+            }
+        }
+        text
+    }
+}
+
+/// The parser splits text at escapes and entities; adjacent runs are joined back up.
+fn push_inline(inlines: &mut Vec<Inline>, inline: Inline) {
+    if let (Some(Inline::Text(previous)), Inline::Text(text)) = (inlines.last_mut(), &inline) {
+        previous.push_str(text);
+    } else {
+        inlines.push(inline);
+    }
+}
+
+fn task_marker(marker: &str) -> Option<bool> {
+    match marker {
+        "[x]" => Some(true),
+        "[ ]" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_inline(event: &Event<'_>) -> bool {
+    matches!(
+        event,
+        Event::Text(_)
+            | Event::Code(_)
+            | Event::SoftBreak
+            | Event::HardBreak
+            | Event::Start(
+                Tag::Emphasis | Tag::Strong | Tag::Strikethrough | Tag::Link(..) | Tag::Image(..)
+            )
+    )
+}
+
+fn write_blocks(blocks: &[Block], separator: &str) -> String {
+    blocks
+        .iter()
+        .map(write_block)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn write_block(block: &Block) -> String {
+    match block {
+        Block::Heading { level, content } => {
+            format!("{} {}", "#".repeat(*level as usize), write_inlines(content))
+        }
+        Block::Paragraph(content) => write_inlines(content),
+        Block::List(list) => write_list(list),
+        Block::CodeBlock { info, code } => {
+            let mut fence = "```".to_string();
+            while code.contains(fence.as_str()) {
+                fence.push('`');
+            }
+            let info = info.as_deref().unwrap_or_default();
+            format!("{fence}{info}\n{code}{fence}")
+        }
+        Block::BlockQuote(blocks) => prefix_lines(&write_blocks(blocks, "\n\n"), "> ", "> "),
+        Block::Table(table) => write_table(table),
+        Block::Rule => "---".to_string(),
+        Block::Html(html) => html.clone(),
+    }
+}
+
+fn write_list(list: &ListBlock) -> String {
+    let (item_separator, block_separator) = if list.tight {
+        ("\n", "\n")
+    } else {
+        ("\n\n", "\n\n")
+    };
+    list.items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let mut marker = match list.start {
+                Some(start) => format!("{}{} ", start + i as u64, list.marker),
+                None => format!("{} ", list.marker),
+            };
+            let indent = " ".repeat(marker.len());
+            match item.checked {
+                Some(true) => marker.push_str("[x] "),
+                Some(false) => marker.push_str("[ ] "),
+                None => {}
+            }
+            let body = write_blocks(&item.blocks, block_separator);
+            prefix_lines(&body, &marker, &indent).trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(item_separator)
+}
+
+fn write_table(table: &TableBlock) -> String {
+    let row = |cells: &[Vec<Inline>]| {
+        let cells: Vec<String> = cells.iter().map(|cell| write_inlines(cell)).collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let rule: Vec<&str> = table
+        .alignments
+        .iter()
+        .map(|a| match a {
+            Alignment::None => "---",
+            Alignment::Left => ":--",
+            Alignment::Center => ":-:",
+            Alignment::Right => "--:",
+        })
+        .collect();
+    let mut lines = vec![row(&table.header), format!("| {} |", rule.join(" | "))];
+    lines.extend(table.rows.iter().map(|r| row(r)));
+    lines.join("\n")
+}
+
+/// Put `first` in front of the first line and `rest` in front of the others, leaving
+/// blank lines without trailing spaces.
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            let prefix = if i == 0 { first } else { rest };
+            if line.is_empty() && i > 0 {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn write_inlines(inlines: &[Inline]) -> String {
+    let mut out = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => {
+                let at_line_start = out.is_empty() || out.ends_with('\n');
+                out.push_str(&escape(text, at_line_start));
+            }
+            Inline::Code(code) => {
+                let mut ticks = "`".to_string();
+                while code.contains(ticks.as_str()) {
+                    ticks.push('`');
+                }
+                let pad = if code.starts_with('`') || code.ends_with('`') {
+                    " "
+                } else {
+                    ""
+                };
+                out.push_str(&format!("{ticks}{pad}{code}{pad}{ticks}"));
+            }
+            Inline::Emphasis(children) => out.push_str(&format!("*{}*", write_inlines(children))),
+            Inline::Strong(children) => out.push_str(&format!("**{}**", write_inlines(children))),
+            Inline::Strikethrough(children) => {
+                out.push_str(&format!("~~{}~~", write_inlines(children)))
+            }
+            Inline::Link {
+                url,
+                title,
+                content,
+            } => {
+                if title.is_empty() && matches!(content.as_slice(), [Inline::Text(t)] if t == url) {
+                    out.push_str(&format!("<{url}>"));
+                } else {
+                    out.push_str(&format!(
+                        "[{}]({})",
+                        write_inlines(content),
+                        destination(url, title)
+                    ));
+                }
+            }
+            Inline::Image { url, title, alt } => out.push_str(&format!(
+                "![{}]({})",
+                write_inlines(alt),
+                destination(url, title)
+            )),
+            Inline::SoftBreak => out.push('\n'),
+            Inline::HardBreak => out.push_str("\\\n"),
+            Inline::Html(html) => out.push_str(html),
+        }
+    }
+    out
+}
+
+fn destination(url: &str, title: &str) -> String {
+    let url = if url.is_empty() || url.contains([' ', '(', ')', '<', '>']) {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    };
+    if title.is_empty() {
+        url
+    } else {
+        format!("{url} \"{}\"", title.replace('"', "\\\""))
+    }
+}
+
+/// Escape the characters in some text that Markdown would otherwise read as syntax.
+fn escape(text: &str, at_line_start: bool) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in chars.iter().enumerate() {
+        let previous = if i > 0 { chars.get(i - 1) } else { None };
+        let next = chars.get(i + 1);
+        let needs_escape = match c {
+            '\\' | '*' | '`' | '[' | ']' | '<' | '~' | '|' => true,
+            // Underscores inside words can't start emphasis.
+            '_' => {
+                !(previous.map_or(false, |p| p.is_alphanumeric())
+                    && next.map_or(false, |n| n.is_alphanumeric()))
+            }
+            '&' => {
+                let rest: String = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '#')
+                    .collect();
+                !rest.is_empty() && chars.get(i + 1 + rest.chars().count()) == Some(&';')
+            }
+            '!' => next == Some(&'['),
+            _ => false,
+        };
+        if needs_escape {
+            out.push('\\');
+        }
+        out.push(*c);
+    }
+    if at_line_start {
+        if let Some(at) = block_marker(&out) {
+            out.insert(at, '\\');
+        }
+    }
+    out
+}
+
+/// If a line of text would be read as the start of some block construct, where to put
+/// a backslash to stop that.
+fn block_marker(line: &str) -> Option<usize> {
+    let indent = line.len() - line.trim_start().len();
+    let trimmed = &line[indent..];
+    match trimmed.chars().next() {
+        Some('#' | '>' | '=' | '-' | '+') => Some(indent),
+        Some(c) if c.is_ascii_digit() => {
+            let digits = trimmed.len()
+                - trimmed
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            let rest = &trimmed[digits..];
+            let is_marker =
+                rest.starts_with(['.', ')']) && (rest.len() == 1 || rest[1..].starts_with(' '));
+            is_marker.then_some(indent + digits)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    const PAGE: &str = r#"# Deploying
+
+Read [the runbook](https://app.nuclino.com/t/b/4e0f2b6a-3c1d-4d8e-9b7a-2f6c5d4e3a21 "Runbook") first, or see <https://example.com>.
+
+## Steps
+
+1. Merge to *main*
+2. Wait for **CI** and ~~pray~~
+3. Run `make deploy`
+
+- [x] Tag the release
+- [ ] Post in #deploys
+
+> Never deploy on a Friday.
+>
+> Seriously.
+
+```sh
+make deploy ENV=prod
+```
+
+| Env | Url | Owner |
+| :-- | :-: | --: |
+| prod | <https://example.com> | ops |
+| staging | none | ops\|dev |
+
+![diagram](https://files.nuclino.com/a.png)
+
+---
+
+<details>
+<summary>Notes</summary>
+</details>
+
+Some snake_case_names and 2 * 3 stay readable.
+"#;
+
+    #[test]
+    fn parsing_structure() {
+        let document = Document::parse(PAGE);
+        assert_eq!(
+            document.headings(),
+            vec![(1, "Deploying".to_string()), (2, "Steps".to_string())]
+        );
+        let lists: Vec<&ListBlock> = document
+            .blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::List(list) => Some(list),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].start, Some(1));
+        assert_eq!(lists[0].marker, '.');
+        assert!(lists[0].tight);
+        assert_eq!(lists[1].marker, '-');
+        assert_eq!(lists[1].items[0].checked, Some(true));
+        assert_eq!(lists[1].items[1].checked, Some(false));
+        assert_eq!(
+            lists[1].items[1].blocks,
+            vec![Block::Paragraph(vec![Inline::Text(
+                "Post in #deploys".into()
+            )])]
+        );
+
+        let table = document
+            .blocks
+            .iter()
+            .find_map(|b| match b {
+                Block::Table(table) => Some(table),
+                _ => None,
+            })
+            .expect("the page has a table");
+        assert_eq!(
+            table.alignments,
+            vec![Alignment::Left, Alignment::Center, Alignment::Right]
+        );
+        assert_eq!(table.rows[1][2], vec![Inline::Text("ops|dev".into())]);
+        assert!(document
+            .blocks
+            .iter()
+            .any(|b| matches!(b, Block::Html(html) if html.starts_with("<details>") && html.ends_with("</details>"))));
+
+        let mut urls = Vec::new();
+        document.for_each_inline(&mut |inline| {
+            if let Inline::Link { url, .. } | Inline::Image { url, .. } = inline {
+                urls.push(url.clone());
+            }
+        });
+        assert_eq!(urls.len(), 4);
+    }
+
+    #[test]
+    fn serializing_round_trips() {
+        let document = Document::parse(PAGE);
+        let written = document.to_markdown();
+        assert_eq!(Document::parse(&written), document);
+        // Already-normalized Markdown comes back unchanged.
+        assert_eq!(Document::parse(&written).to_markdown(), written);
+        assert!(written.contains("Some snake_case_names and 2 \\* 3 stay readable."));
+        assert!(written.contains("- [ ] Post in #deploys"));
+        assert!(written.contains("| staging | none | ops\\|dev |"));
+
+        let tricky = "\\# not a heading\n\n1\\. not a list\n\nloose:\n\n* one\n\n* two\n\n  more\n\n````\n```\n````\n";
+        let document = Document::parse(tricky);
+        assert_eq!(Document::parse(&document.to_markdown()), document);
+    }
+
+    #[test]
+    fn transforming_items() {
+        let item = fixtures::item(
+            fixtures::id(1),
+            "Page",
+            Some("See [docs](http://example.com)."),
+        );
+        let mut document = match item {
+            crate::Page::Item(item) => item.document(),
+            crate::Page::Collection(_) => unreachable!(),
+        };
+        document.for_each_inline_mut(&mut |inline| {
+            if let Inline::Link { url, .. } = inline {
+                *url = url.replace("http://", "https://");
+            }
+        });
+        assert_eq!(document.to_markdown(), "See [docs](https://example.com).\n");
+        assert_eq!(Document::parse("").to_markdown(), "");
+    }
+}
@@ -0,0 +1,301 @@
+//! Exporting a workspace as an [Obsidian](https://obsidian.md) vault. Items become notes
+//! and collections become folders. Links between pages become `[[wikilinks]]`, field
+//! values become note properties, and attachments are saved in an `attachments` folder.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::export::{link_path, sanitize_filename, unique_name, Layout};
+use crate::frontmatter::{FrontMatter, Value};
+use crate::{
+    find_links, Attachments, Client, ExportedPage, LinkKind, NuclinoResult, Page, PageKind,
+    PageTree,
+};
+
+/// The vault folder attachments are saved in.
+pub static ATTACHMENTS_DIR: &str = "attachments";
+
+/// The note properties we write for every page. A field with one of these names is
+/// written with a `field-` prefix so it can't replace ours.
+const RESERVED_KEYS: &[&str] = &[
+    "aliases",
+    "nuclino-id",
+    "nuclino-url",
+    "created",
+    "modified",
+];
+
+/// Write a hydrated tree to `dir` as an Obsidian vault. `attachments` holds the
+/// downloaded files for each item, keyed by item id. Returns a record of every note
+/// written, with paths relative to `dir`.
+///
+/// Obsidian resolves `[[Title]]` by file name, so links use just the note's name when
+/// that's unique in the vault, and the note's path inside the vault when it isn't. A
+/// note whose file name differs from its page's title, because the title had
+/// characters Obsidian can't link to or collided with a sibling's, gets the title as an
+/// alias.
+pub fn write_vault(
+    tree: &PageTree,
+    attachments: &Attachments,
+    dir: &Path,
+) -> NuclinoResult<Vec<ExportedPage>> {
+    // Collections are only folders, so nothing needs their index name; an empty one
+    // can't collide with a title.
+    let layout = Layout::plan_named(tree, "md", "", note_name);
+    let notes: Vec<(Uuid, &PathBuf)> = tree
+        .depth_first()
+        .filter(|node| matches!(node.page(), Page::Item(_)))
+        .filter_map(|node| Some((*node.id(), layout.file(node.id())?)))
+        .collect();
+
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for (_, path) in &notes {
+        *name_counts.entry(stem(path).to_lowercase()).or_default() += 1;
+    }
+    let targets: HashMap<Uuid, String> = notes
+        .iter()
+        .map(|(id, path)| {
+            let name = stem(path);
+            let target = if name_counts.get(&name.to_lowercase()) == Some(&1) {
+                name
+            } else {
+                link_path(&path.with_extension(""))
+            };
+            (*id, target)
+        })
+        .collect();
+
+    std::fs::create_dir_all(dir)?;
+    let mut files: HashMap<Uuid, String> = HashMap::new();
+    let mut taken = HashSet::new();
+    for (id, _) in &notes {
+        for (info, bytes) in attachments.get(id).into_iter().flatten() {
+            let name = unique_name(&note_name(info.filename()), &mut taken);
+            let path = Path::new(ATTACHMENTS_DIR).join(&name);
+            std::fs::create_dir_all(dir.join(ATTACHMENTS_DIR))?;
+            std::fs::write(dir.join(&path), bytes)?;
+            files.insert(*info.id(), link_path(&path));
+        }
+    }
+
+    let mut written = Vec::with_capacity(notes.len());
+    for (id, path) in notes {
+        let Some(Page::Item(item)) = tree.get(&id).map(|node| node.page()) else {
+            continue;
+        };
+        let mut matter = FrontMatter::new();
+        if stem(path) != item.title() {
+            matter.insert("aliases", Value::List(vec![item.title().to_string()]));
+        }
+        matter
+            .string("nuclino-id", &id.to_string())
+            .string("nuclino-url", item.url())
+            .string("created", item.created())
+            .string("modified", item.modified());
+        // Fields are flat properties, as Obsidian expects.
+        let mut fields: Vec<(&String, &String)> = item.field_values().iter().collect();
+        fields.sort();
+        for (name, value) in fields {
+            if RESERVED_KEYS
+                .iter()
+                .any(|key| key.eq_ignore_ascii_case(name))
+            {
+                matter.string(&format!("field-{name}"), value);
+            } else {
+                matter.string(name, value);
+            }
+        }
+
+        let content = wikilinks(
+            item.content().map(String::as_str).unwrap_or_default(),
+            tree,
+            &targets,
+            &files,
+        );
+        let mut note = matter.render();
+        note.push('\n');
+        note.push_str(&content);
+        if !note.ends_with('\n') {
+            note.push('\n');
+        }
+        let full = dir.join(path);
+        if let Some(parent) = full.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&full, note)?;
+        written.push(ExportedPage {
+            id,
+            kind: PageKind::Item,
+            path: path.clone(),
+            modified: item.modified().to_string(),
+        });
+    }
+    Ok(written)
+}
+
+/// Replace links to pages in the vault with wikilinks, and links to downloaded files
+/// with links to the attachments. Other links are left alone.
+fn wikilinks(
+    content: &str,
+    tree: &PageTree,
+    targets: &HashMap<Uuid, String>,
+    files: &HashMap<Uuid, String>,
+) -> String {
+    let mut rewritten = String::with_capacity(content.len());
+    let mut copied = 0;
+    for link in find_links(content) {
+        let original = &content[link.span.clone()];
+        let replacement = match link.kind {
+            LinkKind::File => {
+                let Some(file) = files.get(&link.id) else {
+                    continue;
+                };
+                let text = link.text.as_deref().unwrap_or_default();
+                if original.starts_with('!') {
+                    format!("![[{file}]]")
+                } else {
+                    wikilink(file, text)
+                }
+            }
+            LinkKind::Item | LinkKind::Mention => {
+                let Some(target) = targets.get(&link.id) else {
+                    continue;
+                };
+                let title = tree
+                    .get(&link.id)
+                    .map(|node| node.page().title())
+                    .unwrap_or_default();
+                wikilink(target, link.text.as_deref().unwrap_or(title))
+            }
+        };
+        rewritten.push_str(&content[copied..link.span.start]);
+        rewritten.push_str(&replacement);
+        copied = link.span.end;
+    }
+    rewritten.push_str(&content[copied..]);
+    rewritten
+}
+
+/// `[[target]]`, or `[[target|text]]` when the text shown should differ.
+fn wikilink(target: &str, text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']' | '|'))
+        .collect();
+    if text.is_empty() || text == target {
+        format!("[[{target}]]")
+    } else {
+        format!("[[{target}|{text}]]")
+    }
+}
+
+/// A file name Obsidian can link to: safe on disk, and without the characters that mean
+/// something inside a wikilink.
+fn note_name(title: &str) -> String {
+    sanitize_filename(title).replace(['[', ']', '#', '^', '|'], "-")
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+impl Client {
+    /// Export a workspace as an Obsidian vault. Every item is fetched for its content and
+    /// every attachment is downloaded, so this makes one request per item and two per
+    /// attachment.
+    pub fn export_vault(&self, workspace: &Uuid, dir: &Path) -> NuclinoResult<Vec<ExportedPage>> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        let attachments = self.tree_attachments(&tree, |_| true)?;
+        write_vault(&tree, &attachments, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        let home = format!(
+            "Start with [the deploy guide](https://app.nuclino.com/t/b/{deploy}), then <https://app.nuclino.com/t/b/{other}>.\n\
+             Also [Rollback #1](https://app.nuclino.com/t/b/{rollback}).\n\n\
+             ![diagram](https://files.nuclino.com/files/{image}/diagram.png)\n\
+             [runbook.pdf](https://files.nuclino.com/files/{pdf}/runbook.pdf)\n",
+            deploy = id(3),
+            other = id(5),
+            rollback = id(6),
+            image = id(10),
+            pdf = id(11),
+        );
+        let pages = vec![
+            item_full(
+                id(1),
+                "Home",
+                Some(&home),
+                "2021-12-15T17:02:53.487Z",
+                &[],
+                &[
+                    ("Status", "Current"),
+                    ("Owner", "ops"),
+                    ("Modified", "by hand"),
+                ],
+            ),
+            collection(id(2), "Prod", &[id(3), id(6)]),
+            item(id(3), "Deploy", Some("Prod deploys.")),
+            collection(id(4), "Staging", &[id(5)]),
+            item(id(5), "Deploy", Some("Staging deploys.")),
+            item(id(6), "Rollback #1", Some("Undo.")),
+        ];
+        PageTree::build(workspace(&[id(1), id(2), id(4)]), pages).expect("sample tree should build")
+    }
+
+    #[test]
+    fn writing_a_vault() {
+        let dir = std::env::temp_dir().join(format!("nuclino-vault-{}", std::process::id()));
+        let mut attachments = HashMap::new();
+        attachments.insert(
+            id(1),
+            vec![
+                (file(id(10), id(1), "diagram.png"), vec![1]),
+                (file(id(11), id(1), "runbook.pdf"), vec![2]),
+            ],
+        );
+        let written = write_vault(&sample(), &attachments, &dir).expect("export should succeed");
+        let paths: Vec<&Path> = written.iter().map(|p| p.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("Home.md"),
+                Path::new("Prod/Deploy.md"),
+                Path::new("Prod/Rollback -1.md"),
+                Path::new("Staging/Deploy.md"),
+            ]
+        );
+
+        let home = std::fs::read_to_string(dir.join("Home.md")).expect("note should exist");
+        assert!(home.starts_with("---\nnuclino-id: \"00000000-0000-0000-0000-000000000001\"\n"));
+        assert!(home.contains("modified: \"2021-12-15T17:02:53.487Z\"\n"));
+        assert!(home
+            .contains("field-Modified: \"by hand\"\nOwner: \"ops\"\nStatus: \"Current\"\n---\n"));
+        assert!(home.contains(
+            "Start with [[Prod/Deploy|the deploy guide]], then [[Staging/Deploy|Deploy]].\n"
+        ));
+        assert!(home.contains("Also [[Rollback -1|Rollback #1]]."));
+        assert!(home
+            .contains("![[attachments/diagram.png]]\n[[attachments/runbook.pdf|runbook.pdf]]\n"));
+        assert_eq!(
+            std::fs::read(dir.join("attachments/runbook.pdf")).expect("attachment should exist"),
+            vec![2]
+        );
+
+        let rollback =
+            std::fs::read_to_string(dir.join("Prod/Rollback -1.md")).expect("note should exist");
+        assert!(rollback.starts_with("---\naliases:\n  - \"Rollback #1\"\n"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
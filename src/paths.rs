@@ -0,0 +1,242 @@
+//! Finding pages by their human-readable title paths, like `Runbooks/Deploy`.
+
+use uuid::Uuid;
+
+use crate::{Client, NuclinoError, NuclinoResult, Page, PageTree, TreeNode};
+
+/// What to do when more than one page matches a title path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateTitles {
+    /// Return `NuclinoError::AmbiguousPath` listing every match.
+    #[default]
+    Error,
+    /// Use the match that comes first in sidebar order.
+    First,
+}
+
+/// Options for matching title paths against pages.
+#[derive(Debug, Clone, Default)]
+pub struct PathOptions {
+    /// Match titles without regard to case. Off by default.
+    pub case_insensitive: bool,
+    /// How to handle a path that matches more than one page.
+    pub duplicates: DuplicateTitles,
+}
+
+/// Split a title path into its titles. Titles are separated by `/`; a title that
+/// itself contains a slash can escape it as `\/`, and a backslash as `\\`. Any other
+/// backslash is kept as is. Empty segments are ignored, so leading and trailing
+/// slashes don't matter.
+pub fn split_path(path: &str) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ ('/' | '\\')) => current.push(escaped),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            '/' => segments.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Join titles into a title path, escaping any slashes and backslashes in them. The
+/// inverse of `split_path()`.
+pub fn join_path<S: AsRef<str>>(titles: &[S]) -> String {
+    titles
+        .iter()
+        .map(|t| t.as_ref().replace('\\', "\\\\").replace('/', "\\/"))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl PageTree {
+    /// Find the page at the end of a title path, walking down from the top level of
+    /// the workspace through collections whose titles match each segment.
+    pub fn find_path(&self, path: &str, options: &PathOptions) -> NuclinoResult<&TreeNode> {
+        let titles = split_path(path);
+        if titles.is_empty() {
+            return Err(NuclinoError::NotFound(format!(
+                "page at empty path '{path}'"
+            )));
+        }
+        let mut matches: Vec<&TreeNode> = Vec::new();
+        self.collect_matches(self.roots(), &titles, options, &mut matches);
+        match (matches.len(), options.duplicates) {
+            (0, _) => Err(NuclinoError::NotFound(format!("page at path '{path}'"))),
+            (1, _) | (_, DuplicateTitles::First) => Ok(matches[0]),
+            (_, DuplicateTitles::Error) => Err(NuclinoError::AmbiguousPath {
+                path: path.to_string(),
+                matches: matches.iter().map(|n| *n.id()).collect(),
+            }),
+        }
+    }
+
+    /// The titles of a page and its ancestors, starting from the top level of the workspace.
+    /// Returns `None` if the page isn't in this tree.
+    pub fn path_of(&self, id: &Uuid) -> Option<Vec<&str>> {
+        let mut titles: Vec<&str> = Vec::new();
+        let mut node = self.get(id)?;
+        loop {
+            titles.push(node.page().title());
+            match node.parent().and_then(|p| self.get(p)) {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+        titles.reverse();
+        Some(titles)
+    }
+
+    fn collect_matches<'a>(
+        &'a self,
+        candidates: &[Uuid],
+        titles: &[String],
+        options: &PathOptions,
+        matches: &mut Vec<&'a TreeNode>,
+    ) {
+        let Some((wanted, rest)) = titles.split_first() else {
+            return;
+        };
+        for node in candidates.iter().filter_map(|id| self.get(id)) {
+            let title = node.page().title().trim();
+            let hit = if options.case_insensitive {
+                title.to_lowercase() == wanted.to_lowercase()
+            } else {
+                title == wanted
+            };
+            if !hit {
+                continue;
+            }
+            if rest.is_empty() {
+                matches.push(node);
+            } else {
+                self.collect_matches(node.children(), rest, options, matches);
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Find a page in a workspace by its title path, e.g. `Runbooks/Deploy`, matching
+    /// titles exactly and failing if the path is ambiguous.
+    pub fn page_by_path(&self, workspace: &Uuid, path: &str) -> NuclinoResult<Page> {
+        self.page_by_path_with(workspace, path, &PathOptions::default())
+    }
+
+    /// Find a page in a workspace by its title path, with control over how titles match.
+    pub fn page_by_path_with(
+        &self,
+        workspace: &Uuid,
+        path: &str,
+        options: &PathOptions,
+    ) -> NuclinoResult<Page> {
+        let tree = self.workspace_tree(workspace)?;
+        let node = tree.find_path(path, options)?;
+        Ok(node.page().clone())
+    }
+
+    /// The breadcrumb of titles leading to a page from the top level of its workspace,
+    /// ending with the page's own title. Pass it to `join_path()` to get a string that
+    /// `page_by_path()` will accept.
+    pub fn page_path(&self, id: &Uuid) -> NuclinoResult<Vec<String>> {
+        let page = self.page(id)?;
+        let tree = self.workspace_tree(page.workspace())?;
+        let titles = tree
+            .path_of(id)
+            .ok_or_else(|| NuclinoError::NotFound(format!("page {id} in its workspace tree")))?;
+        Ok(titles.into_iter().map(str::to_string).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2), id(3)]),
+            item(id(2), "Deploy", None),
+            item(id(3), "Roll/back", None),
+            collection(id(4), "runbooks", &[id(5)]),
+            item(id(5), "Deploy", None),
+        ];
+        PageTree::build(workspace(&[id(1), id(4)]), pages).expect("sample tree should build")
+    }
+
+    #[test]
+    fn splitting_and_joining() {
+        assert_eq!(split_path("/Runbooks//Deploy/"), vec!["Runbooks", "Deploy"]);
+        assert_eq!(
+            split_path(r"Runbooks/Roll\/back"),
+            vec!["Runbooks", "Roll/back"]
+        );
+        assert_eq!(
+            join_path(&["Runbooks", "Roll/back"]),
+            r"Runbooks/Roll\/back"
+        );
+        let titles = [r"C:\", r"Back\slash", r"ends in \", "a/b"];
+        let joined = join_path(&titles);
+        assert_eq!(joined, r"C:\\/Back\\slash/ends in \\/a\/b");
+        assert_eq!(split_path(&joined), titles);
+        assert_eq!(split_path(r"C:\Users"), vec![r"C:\Users"]);
+    }
+
+    #[test]
+    fn exact_matches() {
+        let tree = sample();
+        let options = PathOptions::default();
+        let found = tree
+            .find_path("Runbooks/Deploy", &options)
+            .expect("exact match should be found");
+        assert_eq!(found.id(), &id(2));
+        let found = tree
+            .find_path(r"Runbooks/Roll\/back", &options)
+            .expect("escaped slashes should match");
+        assert_eq!(found.id(), &id(3));
+        assert!(matches!(
+            tree.find_path("Runbooks/Missing", &options),
+            Err(NuclinoError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn case_insensitive_duplicates() {
+        let tree = sample();
+        let mut options = PathOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let result = tree.find_path("RUNBOOKS/deploy", &options);
+        assert!(
+            matches!(result, Err(NuclinoError::AmbiguousPath { ref matches, .. }) if matches == &[id(2), id(5)])
+        );
+        options.duplicates = DuplicateTitles::First;
+        let found = tree
+            .find_path("RUNBOOKS/deploy", &options)
+            .expect("first match should be returned");
+        assert_eq!(found.id(), &id(2));
+    }
+
+    #[test]
+    fn breadcrumbs() {
+        let tree = sample();
+        assert_eq!(tree.path_of(&id(3)), Some(vec!["Runbooks", "Roll/back"]));
+        assert_eq!(tree.path_of(&id(4)), Some(vec!["runbooks"]));
+        assert!(tree.path_of(&id(42)).is_none());
+    }
+}
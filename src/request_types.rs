@@ -1,11 +1,19 @@
 //! Types you'll use when sending new data to Nuclino.
 
-use serde::Serialize;
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use uuid::Uuid;
 
+use crate::{content_hash, NewPageError, Page};
+
+/// The longest page title `try_build()` will accept, in characters. Nuclino doesn't
+/// document a limit, so this is a conservative guess.
+pub const MAX_TITLE_LENGTH: usize = 255;
+
 /// An enum used by NewPage to represent the kind of page being created.
-#[derive(Debug, Clone, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum PageKind {
     /// Creating a regular wiki page.
@@ -71,7 +79,8 @@ impl NewPageBuilder {
         }
     }
 
-    /// Finalize your new page. Doesn't (yet) try to ensure you've set one of parent_id or workspace_id.
+    /// Finalize your new page. Doesn't try to ensure you've set one of parent_id or workspace_id;
+    /// use `try_build()` if you want that checked.
     pub fn build(&self) -> NewPage {
         let content = if matches!(self.object, PageKind::Collection) {
             None
@@ -88,6 +97,31 @@ impl NewPageBuilder {
         }
     }
 
+    /// Finalize your new page, checking it for the mistakes Nuclino would reject:
+    /// no workspace or parent, content on a collection, or a title that is set but
+    /// empty or longer than `MAX_TITLE_LENGTH` characters. An unset title is fine.
+    pub fn try_build(&self) -> Result<NewPage, NewPageError> {
+        if self.workspace_id.is_none() && self.parent_id.is_none() {
+            return Err(NewPageError::MissingLocation);
+        }
+        if matches!(self.object, PageKind::Collection) && self.content.is_some() {
+            return Err(NewPageError::ContentOnCollection);
+        }
+        if let Some(title) = self.title.as_ref() {
+            if title.trim().is_empty() {
+                return Err(NewPageError::EmptyTitle);
+            }
+            let length = title.chars().count();
+            if length > MAX_TITLE_LENGTH {
+                return Err(NewPageError::TitleTooLong {
+                    length,
+                    max: MAX_TITLE_LENGTH,
+                });
+            }
+        }
+        Ok(self.build())
+    }
+
     /// Set the title of the new page.
     pub fn title(&mut self, title: &str) -> &mut Self {
         self.title = Some(title.to_string());
@@ -122,6 +156,107 @@ impl NewPageBuilder {
     }
 }
 
+/// Typestate marker for a `TypedPageBuilder` building an item.
+#[derive(Debug, Clone)]
+pub struct ItemPage;
+/// Typestate marker for a `TypedPageBuilder` building a collection.
+#[derive(Debug, Clone)]
+pub struct CollectionPage;
+/// Typestate marker for a `TypedPageBuilder` that doesn't yet know where its page goes.
+#[derive(Debug, Clone)]
+pub struct Unplaced;
+/// Typestate marker for a `TypedPageBuilder` that has a workspace or parent.
+#[derive(Debug, Clone)]
+pub struct Placed;
+
+/// A variation on `NewPageBuilder` that uses the type system to enforce what it can.
+/// `build()` only exists once you've called `workspace()` or `parent()`, and `content()`
+/// only exists when building an item. Title checks still happen at runtime.
+///
+/// ```
+/// use nuclino_rs::{TypedPageBuilder, Uuid};
+///
+/// let workspace = Uuid::nil();
+/// let page = TypedPageBuilder::item()
+///     .title("Runbook")
+///     .content("# Deploying")
+///     .workspace(&workspace)
+///     .build()
+///     .expect("a valid page");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TypedPageBuilder<Kind, Location> {
+    inner: NewPageBuilder,
+    _state: PhantomData<(Kind, Location)>,
+}
+
+impl TypedPageBuilder<ItemPage, Unplaced> {
+    /// Start building a new item page.
+    pub fn item() -> Self {
+        Self {
+            inner: NewPageBuilder::item(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl TypedPageBuilder<CollectionPage, Unplaced> {
+    /// Start building a new collection page.
+    pub fn collection() -> Self {
+        Self {
+            inner: NewPageBuilder::collection(),
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<Kind, Location> TypedPageBuilder<Kind, Location> {
+    /// Set the title of the new page.
+    pub fn title(mut self, title: &str) -> Self {
+        self.inner.title(title);
+        self
+    }
+
+    /// Choose where to create this page in a list of existing children of the page's intended parent.
+    pub fn index(mut self, index: usize) -> Self {
+        self.inner.index(index);
+        self
+    }
+
+    /// Create this new page at the top level of the workspace with this id.
+    pub fn workspace(mut self, id: &Uuid) -> TypedPageBuilder<Kind, Placed> {
+        self.inner.workspace(id);
+        TypedPageBuilder {
+            inner: self.inner,
+            _state: PhantomData,
+        }
+    }
+
+    /// Create this new page as a child of a specific parent page.
+    pub fn parent(mut self, id: &Uuid) -> TypedPageBuilder<Kind, Placed> {
+        self.inner.parent(id);
+        TypedPageBuilder {
+            inner: self.inner,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<Location> TypedPageBuilder<ItemPage, Location> {
+    /// Provide markdown-formatted content for this new item.
+    pub fn content(mut self, content: &str) -> Self {
+        self.inner.content(content);
+        self
+    }
+}
+
+impl<Kind> TypedPageBuilder<Kind, Placed> {
+    /// Finalize your new page. The only errors left to catch at this point are title problems.
+    pub fn build(self) -> Result<NewPage, NewPageError> {
+        self.inner.try_build()
+    }
+}
+
 /// This structure is used by the update endpoints for Items and Collections.
 /// It's simple enough that you can create it directly.
 #[skip_serializing_none]
@@ -134,11 +269,52 @@ pub struct ModifyItem {
     pub content: Option<String>,
 }
 
+/// What a caller expects a page to look like before it's updated. Used by
+/// `Client::page_update_if_unmodified()` to avoid overwriting someone else's changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Precondition {
+    /// The page's last-modified time, as returned by `Page::modified()`.
+    ModifiedAt(String),
+    /// A `content_hash()` of the page's content exactly as `Item::content()` returns it,
+    /// with no newline added. Collections hash as empty content. `content_of()` makes
+    /// one from a page. This is not the hash a `Manifest` records, which is of the
+    /// exported file's body.
+    ContentHash(String),
+}
+
+impl Precondition {
+    /// Expect the page to be exactly as it is now.
+    pub fn unmodified(page: &Page) -> Self {
+        Precondition::ModifiedAt(page.modified().to_string())
+    }
+
+    /// Expect the page to have the content it has now, whenever it was last modified.
+    pub fn content_of(page: &Page) -> Self {
+        Precondition::ContentHash(content_hash(raw_content(page)))
+    }
+
+    /// Check whether a page still meets this precondition.
+    pub fn holds_for(&self, page: &Page) -> bool {
+        match self {
+            Precondition::ModifiedAt(modified) => page.modified() == modified,
+            Precondition::ContentHash(hash) => content_hash(raw_content(page)) == *hash,
+        }
+    }
+}
+
+/// A page's content for hashing, which for collections and empty items is "".
+fn raw_content(page: &Page) -> &str {
+    match page {
+        Page::Item(item) => item.content().map(String::as_str).unwrap_or_default(),
+        Page::Collection(_) => "",
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use uuid::uuid;
 
-    use super::NewPageBuilder;
+    use super::*;
 
     #[test]
     fn new_page_builder() {
@@ -154,4 +330,77 @@ mod tests {
         assert_eq!(page.parent_id, Some(parent));
         assert!(page.workspace_id.is_none());
     }
+
+    #[test]
+    fn try_build_requires_location() {
+        let result = NewPageBuilder::item().title("Nowhere").try_build();
+        assert!(matches!(result, Err(NewPageError::MissingLocation)));
+    }
+
+    #[test]
+    fn try_build_rejects_bad_pages() {
+        let workspace = uuid!("127a8c4a-b3c6-4a42-8fef-b6c521e6c8cf");
+        let result = NewPageBuilder::collection()
+            .content("collections don't get content")
+            .workspace(&workspace)
+            .try_build();
+        assert!(matches!(result, Err(NewPageError::ContentOnCollection)));
+
+        let result = NewPageBuilder::item()
+            .title("   ")
+            .workspace(&workspace)
+            .try_build();
+        assert!(matches!(result, Err(NewPageError::EmptyTitle)));
+
+        let long = "x".repeat(MAX_TITLE_LENGTH + 1);
+        let result = NewPageBuilder::item()
+            .title(&long)
+            .workspace(&workspace)
+            .try_build();
+        assert!(matches!(
+            result,
+            Err(NewPageError::TitleTooLong { length, .. }) if length == MAX_TITLE_LENGTH + 1
+        ));
+
+        let page = NewPageBuilder::collection()
+            .title("Runbooks")
+            .workspace(&workspace)
+            .try_build()
+            .expect("a collection with a title and workspace is valid");
+        assert_eq!(page.workspace_id, Some(workspace));
+    }
+
+    #[test]
+    fn typed_builder() {
+        let parent = uuid!("e9e648b3-8ce3-410d-8ef8-51b46c63cdaf");
+        let page = TypedPageBuilder::item()
+            .content("This is *markdown*")
+            .parent(&parent)
+            .title("Placed after the fact")
+            .build()
+            .expect("the typed builder should produce a valid page");
+        assert_eq!(page.parent_id, Some(parent));
+        assert_eq!(page.content, Some("This is *markdown*".to_string()));
+
+        let result = TypedPageBuilder::collection()
+            .title("")
+            .workspace(&parent)
+            .build();
+        assert!(matches!(result, Err(NewPageError::EmptyTitle)));
+    }
+
+    #[test]
+    fn preconditions() {
+        let id = uuid!("aaf6d580-565d-497b-9ff3-b32075de3f4c");
+        let page = crate::fixtures::item(id, "Status", Some("all good"));
+        assert!(Precondition::unmodified(&page).holds_for(&page));
+        assert!(!Precondition::ModifiedAt("2020-01-01T00:00:00.000Z".into()).holds_for(&page));
+        assert!(Precondition::ContentHash(content_hash("all good")).holds_for(&page));
+        assert!(!Precondition::ContentHash(content_hash("all good\n")).holds_for(&page));
+        assert!(Precondition::content_of(&page).holds_for(&page));
+        let edited = crate::fixtures::item(id, "Status", Some("all bad"));
+        assert!(!Precondition::content_of(&page).holds_for(&edited));
+        let collection = crate::fixtures::collection(id, "Status", &[]);
+        assert!(Precondition::ContentHash(content_hash("")).holds_for(&collection));
+    }
 }
@@ -23,10 +23,8 @@ pub trait ResponseInfo {
     fn message(&self) -> String;
     /// Check if this request was successful.
     fn is_success(&self) -> bool;
-    /// Check if this response reports a server error.
+    /// Check if this response reports a failure on Nuclino's side.
     fn is_server_error(&self) -> bool;
-    /// Check if this response reports an error in the client's request.
-    fn is_client_error(&self) -> bool;
     /// The data payload of this response, if one exists.
     fn data(&self) -> Option<&Self::D>;
 }
@@ -56,10 +54,6 @@ where
     fn is_server_error(&self) -> bool {
         self.status == "error"
     }
-
-    fn is_client_error(&self) -> bool {
-        self.status == "fail"
-    }
 }
 
 /// A list response structure, returned by any endpoint that responds
@@ -0,0 +1,238 @@
+//! Searching every team and workspace the client can see at once, with each result
+//! labeled with where it was found.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use urlencoding::encode;
+use uuid::Uuid;
+
+use crate::{Client, List, NuclinoResult, Page, Team, Workspace, PAGE_LIMIT};
+
+/// One result from `Client::search_everywhere()`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    /// The page found, without content. Items have their search highlight.
+    pub page: Page,
+    /// The name of the page's workspace.
+    pub workspace: String,
+    /// The name of the team that owns the workspace.
+    pub team: String,
+}
+
+impl Client {
+    /// Search every team and workspace this client can see. Each team is searched as a
+    /// whole, following pagination past the server's 100 results, and so is any
+    /// workspace whose team isn't in the team list. The results are merged by rank, so
+    /// every search's best result comes before any search's second best, and a page
+    /// found more than once is listed once, where it first appears.
+    ///
+    /// This makes a request for each page of teams and workspaces, and one for each page
+    /// of results from each search.
+    pub fn search_everywhere(&self, text: &str) -> NuclinoResult<Vec<SearchResult>> {
+        let teams = self.all_teams()?;
+        let workspaces = self.all_workspaces()?;
+
+        let mut ranked: Vec<Vec<Page>> = Vec::new();
+        for team in &teams {
+            ranked.push(self.search_all("teamId", team.id(), text)?);
+        }
+        let team_ids: HashSet<&Uuid> = teams.iter().map(|team| team.id()).collect();
+        for workspace in workspaces
+            .iter()
+            .filter(|workspace| !team_ids.contains(workspace.team_id()))
+        {
+            ranked.push(self.search_all("workspaceId", workspace.id(), text)?);
+        }
+
+        Ok(label(interleave(ranked), &teams, &workspaces))
+    }
+
+    /// Every result of one search, a page of results at a time.
+    fn search_all(&self, scope: &str, id: &Uuid, text: &str) -> NuclinoResult<Vec<Page>> {
+        let mut pages: Vec<Page> = Vec::new();
+        let mut after: Option<Uuid> = None;
+        loop {
+            let mut url = format!(
+                "{}/v0/items?{scope}={id}&search={}&limit={PAGE_LIMIT}",
+                self.baseurl,
+                encode(text)
+            );
+            if let Some(last) = after {
+                url.push_str(&format!("&after={last}"));
+            }
+            let batch = self.get::<List<Page>>(url)?.as_vec();
+            let full = batch.len() == PAGE_LIMIT as usize;
+            after = batch.last().map(|page| *page.id());
+            pages.extend(batch);
+            if !full || after.is_none() {
+                return Ok(pages);
+            }
+        }
+    }
+}
+
+/// Merge ranked lists: first place from each list, then second place from each, and so
+/// on, dropping pages already taken.
+fn interleave(ranked: Vec<Vec<Page>>) -> Vec<Page> {
+    let mut seen: HashSet<Uuid> = HashSet::new();
+    let mut merged: Vec<Page> = Vec::new();
+    let mut lists: Vec<std::vec::IntoIter<Page>> = ranked.into_iter().map(Vec::into_iter).collect();
+    loop {
+        let mut any = false;
+        for list in lists.iter_mut() {
+            if let Some(page) = list.next() {
+                any = true;
+                if seen.insert(*page.id()) {
+                    merged.push(page);
+                }
+            }
+        }
+        if !any {
+            return merged;
+        }
+    }
+}
+
+/// Attach workspace and team names to pages. A workspace or team the client couldn't
+/// list is labeled with its id.
+fn label(pages: Vec<Page>, teams: &[Team], workspaces: &[Workspace]) -> Vec<SearchResult> {
+    let team_names: HashMap<&Uuid, &str> =
+        teams.iter().map(|team| (team.id(), team.name())).collect();
+    let spaces: HashMap<&Uuid, &Workspace> =
+        workspaces.iter().map(|space| (space.id(), space)).collect();
+    pages
+        .into_iter()
+        .map(|page| {
+            let space = spaces.get(page.workspace());
+            let workspace = space
+                .map(|space| space.name().to_string())
+                .unwrap_or_else(|| page.workspace().to_string());
+            let team = space
+                .map(|space| {
+                    team_names
+                        .get(space.team_id())
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| space.team_id().to_string())
+                })
+                .unwrap_or_default();
+            SearchResult {
+                page,
+                workspace,
+                team,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    fn titles(pages: &[Page]) -> Vec<&str> {
+        pages.iter().map(|page| page.title()).collect()
+    }
+
+    #[test]
+    fn interleaving() {
+        let merged = interleave(vec![
+            vec![
+                item(id(1), "a1", None),
+                item(id(2), "a2", None),
+                item(id(3), "a3", None),
+            ],
+            vec![],
+            vec![item(id(4), "b1", None), item(id(1), "a1 again", None)],
+            vec![item(id(2), "a2 again", None)],
+        ]);
+        assert_eq!(titles(&merged), vec!["a1", "b1", "a2 again", "a3"]);
+        assert!(interleave(Vec::new()).is_empty());
+    }
+
+    /// The team that owns the fixture workspace.
+    fn team() -> Team {
+        serde_json::from_value(json!({
+            "object": "team",
+            "id": "020f9737-7b21-442b-85eb-bd420e5593b2",
+            "url": "https://app.nuclino.com/ops",
+            "name": "Ops",
+            "createdAt": "2021-12-15T15:54:23.598Z",
+            "createdUserId": USER_ID,
+        }))
+        .expect("fixture team should deserialize")
+    }
+
+    fn listing<T: Serialize>(results: &[T]) -> (u16, serde_json::Value) {
+        (
+            200,
+            json!({ "status": "success", "data": { "object": "list", "results": results } }),
+        )
+    }
+
+    #[test]
+    fn labeling() {
+        let labeled = label(
+            vec![item(id(1), "Deploy", None)],
+            &[team()],
+            &[workspace(&[])],
+        );
+        assert_eq!(labeled[0].workspace, "General");
+        assert_eq!(labeled[0].team, "Ops");
+
+        let unknown = label(vec![item(id(1), "Deploy", None)], &[], &[]);
+        assert_eq!(unknown[0].workspace, WORKSPACE_ID);
+        assert_eq!(unknown[0].team, "");
+    }
+
+    #[test]
+    fn searching_past_one_page() {
+        // A workspace in a team the client can't list is searched on its own.
+        let elsewhere = id(500);
+        let other: serde_json::Value = json!({
+            "object": "workspace",
+            "id": elsewhere,
+            "teamId": id(501),
+            "name": "Shared with me",
+            "createdAt": "2021-12-15T15:54:23.598Z",
+            "createdUserId": USER_ID,
+            "fields": [],
+            "childIds": [],
+        });
+        let first: Vec<Page> = (1..=100)
+            .map(|n| item(id(n), &format!("Result {n}"), None))
+            .collect();
+        let (url, server) = serve(vec![
+            listing(&[team()]),
+            listing(&[json!(workspace(&[])), other]),
+            listing(&first),
+            listing(&[item(id(101), "Result 101", None)]),
+            listing(&[item(id(200), "Elsewhere", None)]),
+        ]);
+        let results = local_client(&url)
+            .search_everywhere("deploy")
+            .expect("searching should work");
+        assert_eq!(results.len(), 102);
+        assert_eq!(results[0].page.title(), "Result 1");
+        assert_eq!(results[1].page.title(), "Elsewhere");
+        assert_eq!(results[101].page.title(), "Result 101");
+
+        let requests = server.join().expect("the server should finish");
+        let team_search = format!(
+            "GET /v0/items?teamId={}&search=deploy&limit=100",
+            team().id()
+        );
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[2], format!("{team_search} HTTP/1.1"));
+        assert_eq!(
+            requests[3],
+            format!("{team_search}&after={} HTTP/1.1", id(100))
+        );
+        assert_eq!(
+            requests[4],
+            format!("GET /v0/items?workspaceId={elsewhere}&search=deploy&limit=100 HTTP/1.1")
+        );
+    }
+}
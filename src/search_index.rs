@@ -0,0 +1,784 @@
+//! A local full-text index of a workspace's pages, for searching without the network
+//! and without the server's limit of 100 results. Build it from hydrated pages, save it
+//! to disk, and keep it current by applying the [`ChangeEvent`]s a
+//! [`ChangeWatcher`](crate::ChangeWatcher) reports.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::links::url_spans;
+use crate::{ChangeEvent, Client, NuclinoResult, Page};
+
+/// Options for `SearchIndex::search()`.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// How much more a match in a title counts than a match in content.
+    pub title_boost: f64,
+    /// The most hits to return.
+    pub limit: usize,
+    /// About how many words of content each hit's highlight shows.
+    pub snippet_words: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            title_boost: 3.0,
+            limit: 20,
+            snippet_words: 16,
+        }
+    }
+}
+
+/// One page that matched a search.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchHit {
+    /// The page's id.
+    pub id: Uuid,
+    /// The page's title.
+    pub title: String,
+    /// How well the page matched. Only meaningful relative to other hits from the same
+    /// search.
+    pub score: f64,
+    /// A passage from the page with the matching words wrapped in `<b>` tags, in the
+    /// same form as the `highlight` the server returns with its search results, so
+    /// `Highlight::parse()` reads it. Text outside the tags is HTML-escaped. For a page
+    /// that matched only in its title, this is the title.
+    pub highlight: String,
+}
+
+/// A parsed search. Words must all appear in a page's title or content, in any order.
+///
+/// - `deploy` matches the word, ignoring case.
+/// - `deplo*` matches any word starting with `deplo`.
+/// - `"roll back"` matches the words next to each other, in that order.
+/// - `status:current` or `"due date":"next week"` matches pages whose field of that
+///   name has that value, ignoring case.
+///
+/// Words are runs of letters and digits, so punctuation inside a word splits it into a
+/// phrase: `e-mail` is the same as `"e mail"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+    filters: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+impl SearchQuery {
+    /// Parse a search. Anything that isn't a filter or phrase is a word, so parsing
+    /// can't fail; an unterminated quote runs to the end of the search.
+    pub fn parse(search: &str) -> Self {
+        let mut query = Self::default();
+        let mut rest = search.trim_start();
+        while !rest.is_empty() {
+            let (text, quoted, after) = next_part(rest);
+            rest = after;
+            if let Some(after_colon) = rest.strip_prefix(':').filter(|_| quoted) {
+                let (value, _, after) = next_part(after_colon);
+                query
+                    .filters
+                    .push((text.to_lowercase(), value.to_lowercase()));
+                rest = after;
+            } else if let Some((name, value)) = text
+                .split_once(':')
+                .filter(|(name, _)| !quoted && !name.is_empty())
+            {
+                let value = if value.is_empty() {
+                    let (value, _, after) = next_part(rest.trim_start());
+                    rest = after;
+                    value
+                } else {
+                    value.to_string()
+                };
+                query
+                    .filters
+                    .push((name.to_lowercase(), value.to_lowercase()));
+            } else {
+                query.add_terms(&text, quoted);
+            }
+            rest = rest.trim_start();
+        }
+        query
+    }
+
+    /// Whether the search has nothing to look for.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.filters.is_empty()
+    }
+
+    fn add_terms(&mut self, text: &str, quoted: bool) {
+        let prefix = !quoted && text.ends_with('*');
+        let mut words: Vec<String> = tokens(text).into_iter().map(|(word, _)| word).collect();
+        if prefix {
+            let Some(last) = words.pop() else {
+                return;
+            };
+            if !words.is_empty() {
+                self.terms.push(phrase(words));
+            }
+            self.terms.push(Term::Prefix(last));
+        } else if !words.is_empty() {
+            self.terms.push(phrase(words));
+        }
+    }
+}
+
+fn phrase(mut words: Vec<String>) -> Term {
+    if words.len() == 1 {
+        Term::Word(words.remove(0))
+    } else {
+        Term::Phrase(words)
+    }
+}
+
+/// The next quoted string or bare word, whether it was quoted, and what follows it. A
+/// bare word stops at whitespace, or at a quote after a colon, as in `status:"in use"`.
+fn next_part(text: &str) -> (String, bool, &str) {
+    if let Some(quoted) = text.strip_prefix('"') {
+        return match quoted.find('"') {
+            Some(end) => (quoted[..end].to_string(), true, &quoted[end + 1..]),
+            None => (quoted.to_string(), true, ""),
+        };
+    }
+    let mut end = text.find(char::is_whitespace).unwrap_or(text.len());
+    if let Some(colon) = text[..end].find(":\"") {
+        end = colon + 1;
+    }
+    (text[..end].to_string(), false, &text[end..])
+}
+
+/// Lowercased words and where they are in the text.
+fn tokens(text: &str) -> Vec<(String, Range<usize>)> {
+    let mut found = Vec::new();
+    let mut start = None;
+    for (at, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(at),
+            (false, Some(from)) => {
+                found.push((text[from..at].to_lowercase(), from..at));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(from) = start {
+        found.push((text[from..].to_lowercase(), from..text.len()));
+    }
+    found
+}
+
+/// The words of some content, leaving out urls so link targets don't match searches.
+fn content_tokens(content: &str) -> Vec<(String, Range<usize>)> {
+    let urls = url_spans(content);
+    tokens(content)
+        .into_iter()
+        .filter(|(_, range)| !urls.iter().any(|url| url.contains(&range.start)))
+        .collect()
+}
+
+/// What the index keeps about a page, and all that's written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedPage {
+    title: String,
+    content: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl IndexedPage {
+    fn from_page(page: &Page) -> Self {
+        match page {
+            Page::Item(item) => IndexedPage {
+                title: item.title().to_string(),
+                content: item.content().cloned().unwrap_or_default(),
+                fields: item
+                    .field_values()
+                    .iter()
+                    .map(|(name, value)| (name.to_lowercase(), value.to_lowercase()))
+                    .collect(),
+            },
+            Page::Collection(collection) => IndexedPage {
+                title: collection.title().to_string(),
+                content: String::new(),
+                fields: BTreeMap::new(),
+            },
+        }
+    }
+}
+
+/// Where a word appears in one page, as positions in the title's and content's words.
+#[derive(Debug, Clone, Default)]
+struct Positions {
+    title: Vec<usize>,
+    content: Vec<usize>,
+}
+
+/// A full-text index of pages. Add pages with `add_page()`, or build one with
+/// `from_pages()` or `Client::build_search_index()`; adding a page again replaces what
+/// was known about it. Items must be hydrated to have their content indexed.
+/// Collections are indexed by title.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    pages: BTreeMap<Uuid, IndexedPage>,
+    postings: BTreeMap<String, BTreeMap<Uuid, Positions>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedIndex {
+    pages: BTreeMap<Uuid, IndexedPage>,
+}
+
+impl SearchIndex {
+    /// An empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build an index from a set of pages.
+    pub fn from_pages<'a, I>(pages: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Page>,
+    {
+        let mut index = Self::new();
+        for page in pages {
+            index.add_page(page);
+        }
+        index
+    }
+
+    /// Read an index written by `save()`.
+    pub fn load(path: &Path) -> NuclinoResult<Self> {
+        let saved: SavedIndex = serde_json::from_slice(&std::fs::read(path)?)?;
+        let mut index = Self::new();
+        for (id, page) in saved.pages {
+            index.insert(id, page);
+        }
+        Ok(index)
+    }
+
+    /// Write the index to a file. Only the indexed text is stored; the word positions
+    /// are rebuilt when it's loaded.
+    pub fn save(&self, path: &Path) -> NuclinoResult<()> {
+        let saved = SavedIndex {
+            pages: self.pages.clone(),
+        };
+        // Write then rename, so a crash mid-write doesn't leave a corrupt index.
+        let mut partial = path.to_path_buf().into_os_string();
+        partial.push(".tmp");
+        std::fs::write(&partial, serde_json::to_vec(&saved)?)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    /// Add a page to the index, or update it if it's already there.
+    pub fn add_page(&mut self, page: &Page) {
+        self.remove_page(page.id());
+        self.insert(*page.id(), IndexedPage::from_page(page));
+    }
+
+    /// Remove a page from the index.
+    pub fn remove_page(&mut self, id: &Uuid) {
+        let Some(page) = self.pages.remove(id) else {
+            return;
+        };
+        let words: BTreeSet<String> = tokens(&page.title)
+            .into_iter()
+            .chain(content_tokens(&page.content))
+            .map(|(word, _)| word)
+            .collect();
+        for word in words {
+            if let Some(pages) = self.postings.get_mut(&word) {
+                pages.remove(id);
+                if pages.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Update the index for a [`ChangeEvent`] a [`ChangeWatcher`](crate::ChangeWatcher)
+    /// reported. Pages in change events come from listing the workspace, so they have no
+    /// content; an item already in the index keeps the content it was indexed with, and
+    /// only its title and fields are refreshed. `Client::update_search_index()` fetches
+    /// the content too.
+    pub fn apply(&mut self, event: &ChangeEvent) {
+        match event {
+            ChangeEvent::Created(page)
+            | ChangeEvent::Updated(page)
+            | ChangeEvent::Moved { page, .. }
+            | ChangeEvent::Renamed { page, .. } => {
+                let mut indexed = IndexedPage::from_page(page);
+                if let Page::Item(item) = page {
+                    if item.content().is_none() {
+                        if let Some(existing) = self.pages.get(page.id()) {
+                            indexed.content = existing.content.clone();
+                        }
+                    }
+                }
+                self.remove_page(page.id());
+                self.insert(*page.id(), indexed);
+            }
+            ChangeEvent::Deleted { id, .. } => self.remove_page(id),
+        }
+    }
+
+    /// Whether a page is in the index.
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.pages.contains_key(id)
+    }
+
+    /// How many pages are in the index.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Whether the index has no pages.
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Search the index. Hits are ordered best first, with ties broken by title.
+    pub fn search(&self, query: &SearchQuery, options: &SearchOptions) -> Vec<SearchHit> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let total = self.pages.len() as f64;
+        let matches: Vec<BTreeMap<Uuid, Positions>> =
+            query.terms.iter().map(|term| self.matches(term)).collect();
+
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for (id, page) in &self.pages {
+            let passes_filters = query
+                .filters
+                .iter()
+                .all(|(name, value)| page.fields.get(name) == Some(value));
+            if !passes_filters || !matches.iter().all(|found| found.contains_key(id)) {
+                continue;
+            }
+            let mut score = 0.0;
+            let mut title_hits = BTreeSet::new();
+            let mut content_hits = BTreeSet::new();
+            for found in &matches {
+                let count = found.len() as f64;
+                let idf = (1.0 + (total - count + 0.5) / (count + 0.5)).ln();
+                if let Some(positions) = found.get(id) {
+                    score += idf
+                        * (options.title_boost * saturate(positions.title.len())
+                            + saturate(positions.content.len()));
+                    title_hits.extend(positions.title.iter().copied());
+                    content_hits.extend(positions.content.iter().copied());
+                }
+            }
+            let highlight = if content_hits.is_empty() && !title_hits.is_empty() {
+                highlight(&page.title, &tokens(&page.title), &title_hits, usize::MAX)
+            } else {
+                highlight(
+                    &page.content,
+                    &content_tokens(&page.content),
+                    &content_hits,
+                    options.snippet_words,
+                )
+            };
+            hits.push(SearchHit {
+                id: *id,
+                title: page.title.clone(),
+                score,
+                highlight,
+            });
+        }
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.title.cmp(&b.title))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        hits.truncate(options.limit);
+        hits
+    }
+
+    fn insert(&mut self, id: Uuid, page: IndexedPage) {
+        for (at, (word, _)) in tokens(&page.title).into_iter().enumerate() {
+            let positions = self
+                .postings
+                .entry(word)
+                .or_default()
+                .entry(id)
+                .or_default();
+            positions.title.push(at);
+        }
+        for (at, (word, _)) in content_tokens(&page.content).into_iter().enumerate() {
+            let positions = self
+                .postings
+                .entry(word)
+                .or_default()
+                .entry(id)
+                .or_default();
+            positions.content.push(at);
+        }
+        self.pages.insert(id, page);
+    }
+
+    /// The pages a term matches, with the positions of the words that matched.
+    fn matches(&self, term: &Term) -> BTreeMap<Uuid, Positions> {
+        match term {
+            Term::Word(word) => self.postings.get(word).cloned().unwrap_or_default(),
+            Term::Prefix(prefix) => {
+                let mut found: BTreeMap<Uuid, Positions> = BTreeMap::new();
+                let words = self
+                    .postings
+                    .range(prefix.clone()..)
+                    .take_while(|(word, _)| word.starts_with(prefix.as_str()));
+                for (_, pages) in words {
+                    for (id, positions) in pages {
+                        let entry = found.entry(*id).or_default();
+                        entry.title.extend(&positions.title);
+                        entry.content.extend(&positions.content);
+                    }
+                }
+                found
+            }
+            Term::Phrase(words) => {
+                let Some(first) = words.first().and_then(|word| self.postings.get(word)) else {
+                    return BTreeMap::new();
+                };
+                let mut found = BTreeMap::new();
+                for id in first.keys() {
+                    let at = |index: usize, title: bool| -> Vec<usize> {
+                        self.postings
+                            .get(&words[index])
+                            .and_then(|pages| pages.get(id))
+                            .map(|p| {
+                                if title {
+                                    p.title.clone()
+                                } else {
+                                    p.content.clone()
+                                }
+                            })
+                            .unwrap_or_default()
+                    };
+                    let runs = |title: bool| -> Vec<usize> {
+                        let following: Vec<Vec<usize>> =
+                            (1..words.len()).map(|index| at(index, title)).collect();
+                        at(0, title)
+                            .into_iter()
+                            .filter(|start| {
+                                following.iter().enumerate().all(|(offset, positions)| {
+                                    positions.contains(&(start + offset + 1))
+                                })
+                            })
+                            .flat_map(|start| start..start + words.len())
+                            .collect()
+                    };
+                    let positions = Positions {
+                        title: runs(true),
+                        content: runs(false),
+                    };
+                    if !positions.title.is_empty() || !positions.content.is_empty() {
+                        found.insert(*id, positions);
+                    }
+                }
+                found
+            }
+        }
+    }
+}
+
+/// Diminishing returns for repeated matches.
+fn saturate(count: usize) -> f64 {
+    let count = count as f64;
+    count / (count + 1.2)
+}
+
+/// A passage of about `words` words around the first match, with matching words in
+/// `<b>` tags and runs of whitespace collapsed to single spaces.
+fn highlight(
+    text: &str,
+    words: &[(String, Range<usize>)],
+    matched: &BTreeSet<usize>,
+    length: usize,
+) -> String {
+    if words.is_empty() {
+        return String::new();
+    }
+    let first = matched.iter().next().copied().unwrap_or(0);
+    let start = first.saturating_sub(length / 4);
+    let end = start.saturating_add(length).min(words.len());
+    let start = end.saturating_sub(length).min(start);
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push_str("… ");
+    } else {
+        push_escaped(&mut out, text[..words[0].1.start].trim_start());
+    }
+    let mut open = false;
+    for (index, (_, range)) in words.iter().enumerate().take(end).skip(start) {
+        let is_match = matched.contains(&index);
+        if index > start {
+            if open && !is_match {
+                out.push_str("</b>");
+                open = false;
+            }
+            push_escaped(&mut out, &text[words[index - 1].1.end..range.start]);
+        }
+        if is_match && !open {
+            out.push_str("<b>");
+            open = true;
+        }
+        push_escaped(&mut out, &text[range.clone()]);
+    }
+    if open {
+        out.push_str("</b>");
+    }
+    if end < words.len() {
+        out.push_str(" …");
+    } else {
+        push_escaped(&mut out, text[words[end - 1].1.end..].trim_end());
+    }
+    out
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+            continue;
+        }
+        space = false;
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+impl Client {
+    /// Build a search index for a workspace. This fetches every item, so it makes one
+    /// request per item.
+    pub fn build_search_index(&self, workspace: &Uuid) -> NuclinoResult<SearchIndex> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        Ok(SearchIndex::from_pages(
+            tree.depth_first().map(|node| node.page()),
+        ))
+    }
+
+    /// Apply a batch of [`ChangeEvent`]s from a [`ChangeWatcher`](crate::ChangeWatcher) to
+    /// a search index, fetching each created or changed item so its content is indexed.
+    /// An item that's gone by the time it's fetched is removed from the index. Makes one
+    /// request per item event.
+    pub fn update_search_index(
+        &self,
+        index: &mut SearchIndex,
+        events: &[ChangeEvent],
+    ) -> NuclinoResult<()> {
+        // A page can appear in several events; fetch it once.
+        let mut fetched: HashMap<Uuid, Page> = HashMap::new();
+        for event in events {
+            match event {
+                ChangeEvent::Deleted { id, .. } => {
+                    fetched.remove(id);
+                    index.remove_page(id);
+                }
+                ChangeEvent::Created(page)
+                | ChangeEvent::Updated(page)
+                | ChangeEvent::Moved { page, .. }
+                | ChangeEvent::Renamed { page, .. } => {
+                    if let Page::Collection(_) = page {
+                        index.add_page(page);
+                        continue;
+                    }
+                    if !fetched.contains_key(page.id()) {
+                        match self.page(page.id()) {
+                            Ok(full) => {
+                                fetched.insert(*page.id(), full);
+                            }
+                            // Deleted since the event was reported.
+                            Err(e) if e.is_not_found() => {
+                                index.remove_page(page.id());
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    if let Some(full) = fetched.get(page.id()) {
+                        index.add_page(full);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> SearchIndex {
+        let pages = vec![
+            item_full(
+                id(1),
+                "Deploying to production",
+                Some(
+                    "Run the deploy script from a clean checkout.\n\n\
+                     See [the guide](https://example.com/deploy-guide) before you <roll back>.",
+                ),
+                "2021-12-15T17:02:53.487Z",
+                &[],
+                &[("Status", "Current")],
+            ),
+            item_full(
+                id(2),
+                "Rollbacks",
+                Some("To roll back a deploy, revert and run the pipeline again."),
+                "2021-12-15T17:02:53.487Z",
+                &[],
+                &[("Status", "Outdated")],
+            ),
+            item(id(3), "Lunch menu", Some("Soup, then deployment cake.")),
+            collection(id(4), "Deploy docs", &[id(1), id(2)]),
+        ];
+        SearchIndex::from_pages(&pages)
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<Uuid> {
+        hits.iter().map(|hit| hit.id).collect()
+    }
+
+    fn search(index: &SearchIndex, text: &str) -> Vec<SearchHit> {
+        index.search(&SearchQuery::parse(text), &SearchOptions::default())
+    }
+
+    #[test]
+    fn parsing() {
+        let query = SearchQuery::parse(
+            r#"Deploy "roll  back" pipe* e-mail status:"In Use" "Due date":friday owner: ops"#,
+        );
+        assert_eq!(
+            query.terms,
+            vec![
+                Term::Word("deploy".into()),
+                Term::Phrase(vec!["roll".into(), "back".into()]),
+                Term::Prefix("pipe".into()),
+                Term::Phrase(vec!["e".into(), "mail".into()]),
+            ]
+        );
+        assert_eq!(
+            query.filters,
+            vec![
+                ("status".to_string(), "in use".to_string()),
+                ("due date".to_string(), "friday".to_string()),
+                ("owner".to_string(), "ops".to_string()),
+            ]
+        );
+        assert!(SearchQuery::parse("  -- ").is_empty());
+    }
+
+    #[test]
+    fn ranking_and_highlights() {
+        let index = sample();
+        // Title matches rank above content matches; urls aren't indexed.
+        assert_eq!(ids(&search(&index, "deploy")), vec![id(4), id(1), id(2)]);
+        assert!(search(&index, "example").is_empty());
+
+        let short = SearchOptions {
+            snippet_words: 4,
+            ..SearchOptions::default()
+        };
+        let hits = index.search(&SearchQuery::parse("\"roll back\""), &short);
+        assert_eq!(ids(&hits), vec![id(1), id(2)]);
+        assert_eq!(hits[0].highlight, "… before you &lt;<b>roll back</b>&gt;.");
+        assert_eq!(hits[1].highlight, "To <b>roll back</b> a …");
+
+        assert_eq!(
+            ids(&search(&index, "deploy*")),
+            vec![id(1), id(4), id(3), id(2)]
+        );
+        assert_eq!(search(&index, "lunch")[0].highlight, "<b>Lunch</b> menu");
+        assert!(search(&index, "\"back roll\"").is_empty());
+    }
+
+    #[test]
+    fn filters() {
+        let index = sample();
+        assert_eq!(ids(&search(&index, "deploy status:outdated")), vec![id(2)]);
+        assert_eq!(ids(&search(&index, "STATUS:current")), vec![id(1)]);
+        assert!(search(&index, "deploy status:missing").is_empty());
+    }
+
+    #[test]
+    fn updates_and_persistence() {
+        let mut index = sample();
+        index.apply(&ChangeEvent::Deleted {
+            id: id(3),
+            title: "Lunch menu".into(),
+        });
+        assert!(search(&index, "cake").is_empty());
+        index.apply(&ChangeEvent::Updated(item(
+            id(2),
+            "Rollbacks",
+            Some("Ask in chat first."),
+        )));
+        assert!(search(&index, "pipeline").is_empty());
+        assert_eq!(ids(&search(&index, "chat")), vec![id(2)]);
+        // Listed pages have no content; renaming one keeps what was indexed.
+        index.apply(&ChangeEvent::Renamed {
+            page: item(id(2), "Undoing a release", None),
+            from: "Rollbacks".into(),
+        });
+        assert_eq!(ids(&search(&index, "chat")), vec![id(2)]);
+        assert_eq!(ids(&search(&index, "undoing")), vec![id(2)]);
+        assert!(search(&index, "rollbacks").is_empty());
+
+        let path = std::env::temp_dir().join(format!("nuclino-index-{}.json", std::process::id()));
+        index.save(&path).expect("saving should succeed");
+        let loaded = SearchIndex::load(&path).expect("loading should succeed");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(search(&loaded, "deploy"), search(&index, "deploy"));
+        assert_eq!(ids(&search(&loaded, "status:current")), vec![id(1)]);
+    }
+
+    #[test]
+    fn updating_from_the_server() {
+        let mut index = sample();
+        let (url, server) = serve(vec![
+            not_found(),
+            (
+                200,
+                json!({ "status": "success", "data": item(id(9), "Release notes", Some("Shipped the widget.")) }),
+            ),
+        ]);
+        local_client(&url)
+            .update_search_index(
+                &mut index,
+                &[
+                    ChangeEvent::Updated(item(id(2), "Rollbacks", None)),
+                    ChangeEvent::Created(item(id(9), "Release notes", None)),
+                ],
+            )
+            .expect("a page deleted since its event shouldn't stop the update");
+        server.join().expect("the server should finish");
+        assert!(!index.contains(&id(2)));
+        assert_eq!(ids(&search(&index, "widget")), vec![id(9)]);
+    }
+}
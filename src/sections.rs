@@ -0,0 +1,262 @@
+//! Editing parts of a page's Markdown: adding to either end, or replacing the section
+//! under a heading. Everything outside the edited part is left byte-for-byte intact.
+
+use uuid::Uuid;
+
+use crate::{Client, ModifyItem, NuclinoError, NuclinoResult, Page, Precondition};
+
+/// A Markdown heading found in some content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// The heading level, from 1 for `#` to 6 for `######`.
+    pub level: usize,
+    /// The heading text, without the `#` markers or surrounding whitespace.
+    pub text: String,
+    /// The byte offset where the heading line starts.
+    pub start: usize,
+    /// The byte offset just past the heading line, including its newline if it has one.
+    pub body_start: usize,
+    /// The byte offset where this heading's section ends: the start of the next heading
+    /// of the same or a higher level, or the end of the content.
+    pub end: usize,
+}
+
+/// Find every ATX-style (`## Like this`) heading in some Markdown, skipping anything
+/// inside fenced code blocks. Setext headings (underlined with `===` or `---`) aren't
+/// recognized; Nuclino doesn't write them.
+pub fn headings(content: &str) -> Vec<Heading> {
+    let mut found: Vec<Heading> = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        if indent > 3 {
+            continue;
+        }
+        let marker = trimmed.chars().next().unwrap_or(' ');
+        if marker == '`' || marker == '~' {
+            let run = trimmed.chars().take_while(|c| *c == marker).count();
+            if run >= 3 {
+                fence = match fence {
+                    None => Some((marker, run)),
+                    Some((open, len))
+                        if open == marker && run >= len && trimmed[run..].trim().is_empty() =>
+                    {
+                        None
+                    }
+                    still_open => still_open,
+                };
+                continue;
+            }
+        }
+        if fence.is_some() {
+            continue;
+        }
+        if let Some((level, text)) = parse_heading(trimmed) {
+            found.push(Heading {
+                level,
+                text,
+                start,
+                body_start: offset,
+                end: content.len(),
+            });
+        }
+    }
+    // Each section runs until the next heading at its level or above.
+    for i in 0..found.len() {
+        let level = found[i].level;
+        if let Some(next) = found[i + 1..].iter().find(|h| h.level <= level) {
+            found[i].end = next.start;
+        }
+    }
+    found
+}
+
+/// Parse `## Heading text ##` into its level and text.
+fn parse_heading(line: &str) -> Option<(usize, String)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    let rest = rest.trim_end_matches(['\n', '\r']);
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    // An optional closing run of #s is not part of the text.
+    let text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+    Some((level, text.to_string()))
+}
+
+/// Add Markdown to the end of some content, on a new line.
+pub fn append_markdown(content: &str, addition: &str) -> String {
+    let mut result = content.to_string();
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(addition);
+    if !addition.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Add Markdown to the start of some content, followed by a newline.
+pub fn prepend_markdown(content: &str, addition: &str) -> String {
+    let mut result = addition.to_string();
+    if !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(content);
+    result
+}
+
+/// Replace everything under a heading, up to the next heading at the same level or
+/// above, with new Markdown. The heading line itself is kept. `heading` is matched
+/// against heading text, ignoring case; if it starts with `#`s, as in `## On call`, the
+/// level must match too. The first matching heading wins.
+pub fn replace_markdown_section(
+    content: &str,
+    heading: &str,
+    replacement: &str,
+) -> NuclinoResult<String> {
+    let wanted = parse_heading(heading.trim());
+    let found = headings(content).into_iter().find(|h| match &wanted {
+        Some((level, text)) => h.level == *level && h.text.eq_ignore_ascii_case(text),
+        None => h.text.eq_ignore_ascii_case(heading.trim()),
+    });
+    let Some(section) = found else {
+        return Err(NuclinoError::NotFound(format!(
+            "a section headed '{heading}'"
+        )));
+    };
+
+    let mut result = String::with_capacity(content.len() + replacement.len());
+    result.push_str(&content[..section.body_start]);
+    if !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push('\n');
+    result.push_str(replacement.trim_matches('\n'));
+    result.push('\n');
+    if section.end < content.len() {
+        result.push('\n');
+    }
+    result.push_str(&content[section.end..]);
+    Ok(result)
+}
+
+impl Client {
+    /// Add Markdown to the end of an item's content.
+    pub fn append_to_page(&self, id: &Uuid, markdown: &str) -> NuclinoResult<Page> {
+        self.edit_content(id, |content| Ok(append_markdown(content, markdown)))
+    }
+
+    /// Add Markdown to the start of an item's content.
+    pub fn prepend_to_page(&self, id: &Uuid, markdown: &str) -> NuclinoResult<Page> {
+        self.edit_content(id, |content| Ok(prepend_markdown(content, markdown)))
+    }
+
+    /// Replace the section of an item under a heading. See `replace_markdown_section()`
+    /// for how headings are matched.
+    pub fn replace_section(&self, id: &Uuid, heading: &str, markdown: &str) -> NuclinoResult<Page> {
+        self.edit_content(id, |content| {
+            replace_markdown_section(content, heading, markdown)
+        })
+    }
+
+    /// Read an item, transform its content, and write it back if nobody changed it in
+    /// between. A concurrent change produces `NuclinoError::Conflict`.
+    fn edit_content<F>(&self, id: &Uuid, transform: F) -> NuclinoResult<Page>
+    where
+        F: FnOnce(&str) -> NuclinoResult<String>,
+    {
+        let page = self.page(id)?;
+        let Page::Item(item) = &page else {
+            return Err(NuclinoError::NotAnItem(*id));
+        };
+        let content = transform(item.content().map(String::as_str).unwrap_or_default())?;
+        let changes = ModifyItem {
+            title: None,
+            content: Some(content),
+        };
+        self.page_update_if_unmodified(id, &Precondition::unmodified(&page), &changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUNBOOK: &str = "# Runbook\n\nIntro.\n\n## Current on-call\n\nAlice\n\n### Backup\n\nBob\n\n```sh\n# not a heading\n```\n\n## Changelog ##\n\n| date | change |\n|---|---|\n| 2026-01-01 | first |";
+
+    #[test]
+    fn finding_headings() {
+        let found = headings(RUNBOOK);
+        let summary: Vec<(usize, &str)> =
+            found.iter().map(|h| (h.level, h.text.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "Runbook"),
+                (2, "Current on-call"),
+                (3, "Backup"),
+                (2, "Changelog")
+            ]
+        );
+        assert_eq!(Some(found[1].end), RUNBOOK.find("## Changelog"));
+        assert_eq!(found[2].end, found[1].end);
+        assert_eq!(found[0].end, RUNBOOK.len());
+        assert!(parse_heading("#hashtag").is_none());
+        assert_eq!(
+            parse_heading("## C# tips"),
+            Some((2, "C# tips".to_string()))
+        );
+    }
+
+    #[test]
+    fn appending_and_prepending() {
+        let appended = append_markdown(RUNBOOK, "| 2026-02-01 | second |");
+        assert!(appended.starts_with(RUNBOOK));
+        assert!(appended.ends_with("| first |\n| 2026-02-01 | second |\n"));
+        let prepended = prepend_markdown(RUNBOOK, "> Deprecated");
+        assert_eq!(prepended, format!("> Deprecated\n{RUNBOOK}"));
+        assert_eq!(append_markdown("", "first"), "first\n");
+    }
+
+    #[test]
+    fn replacing_sections() {
+        let replaced = replace_markdown_section(RUNBOOK, "current ON-CALL", "Carol\n")
+            .expect("the section should be found");
+        let start = RUNBOOK
+            .find("## Current on-call")
+            .expect("heading is in the fixture");
+        let end = RUNBOOK
+            .find("## Changelog")
+            .expect("heading is in the fixture");
+        assert_eq!(
+            replaced,
+            format!(
+                "{}## Current on-call\n\nCarol\n\n{}",
+                &RUNBOOK[..start],
+                &RUNBOOK[end..]
+            )
+        );
+
+        let last = replace_markdown_section(RUNBOOK, "## Changelog", "Nothing yet.")
+            .expect("level-qualified headings should match");
+        assert!(last.ends_with("## Changelog ##\n\nNothing yet.\n"));
+
+        assert!(replace_markdown_section(RUNBOOK, "### Changelog", "x").is_err());
+        assert!(replace_markdown_section(RUNBOOK, "not a heading", "x").is_err());
+    }
+}
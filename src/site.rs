@@ -0,0 +1,370 @@
+//! Rendering a workspace as a static HTML site: one page per item and collection, laid
+//! out like a Markdown export, with navigation built from the collection structure.
+//! Links between pages become relative links between the HTML files, and attachments
+//! are copied into a directory next to the page that uses them.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{html, Parser};
+use uuid::Uuid;
+
+use crate::export::{link_target, relative_path, relink, sanitize_filename, unique_name, Layout};
+use crate::markdown::parser_options;
+use crate::{Attachments, Client, NuclinoResult, Page, PageTree};
+
+/// The name of the stylesheet written at the root of the site.
+pub static STYLESHEET_NAME: &str = "style.css";
+
+/// Added to a page's file name to name the directory holding its attachments.
+static FILES_SUFFIX: &str = "_files";
+
+static STYLESHEET: &str =
+    "body { display: flex; margin: 0; font-family: system-ui, sans-serif; line-height: 1.5; }
+nav { flex: 0 0 16rem; padding: 1rem; border-right: 1px solid #ddd; min-height: 100vh; }
+nav ul { list-style: none; padding-left: 1rem; margin: 0; }
+nav > ul { padding-left: 0; }
+nav a[aria-current] { font-weight: bold; }
+main { flex: 1; max-width: 48rem; padding: 1rem 2rem; }
+pre { overflow-x: auto; background: #f6f6f6; padding: 0.5rem; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ddd; padding: 0.25rem 0.5rem; }
+img { max-width: 100%; }
+";
+
+/// A record of one HTML file written for a page.
+#[derive(Debug, Clone)]
+pub struct SitePage {
+    /// The page's id.
+    pub id: Uuid,
+    /// Where the page was written, relative to the site directory.
+    pub path: PathBuf,
+    /// The attachments copied for this page, relative to the site directory.
+    pub attachments: Vec<PathBuf>,
+}
+
+/// Write a static HTML site for a hydrated tree to `dir`. `attachments` holds the
+/// downloaded files for each item, keyed by item id; links to files that aren't there
+/// are left pointing at Nuclino. The site's front page is `index.html`, titled with the
+/// workspace's name.
+pub fn render_site(
+    tree: &PageTree,
+    attachments: &Attachments,
+    dir: &Path,
+) -> NuclinoResult<Vec<SitePage>> {
+    // Keep the front page, the stylesheet, and each page's attachment directory from
+    // colliding with pages.
+    let layout = Layout::plan_reserving(
+        tree,
+        "html",
+        "index",
+        None,
+        &["index", STYLESHEET_NAME],
+        Some(FILES_SUFFIX),
+    );
+    let site_title = tree.workspace().name();
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(STYLESHEET_NAME), STYLESHEET)?;
+
+    let home = PathBuf::from("index.html");
+    let body = format!(
+        "<h1>{}</h1>\n{}",
+        escape(site_title),
+        child_list(tree, &layout, tree.roots(), Path::new(""))
+    );
+    write_page(
+        dir,
+        &home,
+        site_title,
+        site_title,
+        &nav(tree, &layout, &home),
+        &body,
+    )?;
+
+    let mut written = Vec::with_capacity(tree.len());
+    for node in tree.depth_first() {
+        let Some(path) = layout.file(node.id()) else {
+            continue;
+        };
+        let here = path.parent().unwrap_or(Path::new(""));
+        let page = node.page();
+        let mut copied = Vec::new();
+        let content = match page {
+            Page::Item(item) => {
+                // Attachments go in a directory named after the page.
+                let stem = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let files_dir = here.join(format!("{stem}{FILES_SUFFIX}"));
+                let mut file_paths: HashMap<Uuid, PathBuf> = HashMap::new();
+                let mut taken = HashSet::new();
+                for (info, bytes) in attachments.get(node.id()).into_iter().flatten() {
+                    let name = unique_name(&sanitize_filename(info.filename()), &mut taken);
+                    let file_path = files_dir.join(name);
+                    std::fs::create_dir_all(dir.join(&files_dir))?;
+                    std::fs::write(dir.join(&file_path), bytes)?;
+                    file_paths.insert(*info.id(), file_path.clone());
+                    copied.push(file_path);
+                }
+                let markdown = relink(
+                    item.content().map(String::as_str).unwrap_or_default(),
+                    tree,
+                    |id| layout.file(id).or_else(|| file_paths.get(id)),
+                    here,
+                );
+                let mut rendered = String::new();
+                html::push_html(&mut rendered, Parser::new_ext(&markdown, parser_options()));
+                rendered
+            }
+            Page::Collection(_) => child_list(tree, &layout, node.children(), here),
+        };
+        let body = format!("<h1>{}</h1>\n{content}", escape(page.title()));
+        write_page(
+            dir,
+            path,
+            page.title(),
+            site_title,
+            &nav(tree, &layout, path),
+            &body,
+        )?;
+        written.push(SitePage {
+            id: *page.id(),
+            path: path.clone(),
+            attachments: copied,
+        });
+    }
+    Ok(written)
+}
+
+/// A list of links to some pages.
+fn child_list(tree: &PageTree, layout: &Layout, children: &[Uuid], here: &Path) -> String {
+    let mut list = String::from("<ul>\n");
+    for child in children.iter().filter_map(|id| tree.get(id)) {
+        if let Some(path) = layout.file(child.id()) {
+            list.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape(&link_target(&relative_path(here, path))),
+                escape(child.page().title())
+            ));
+        }
+    }
+    list.push_str("</ul>");
+    list
+}
+
+/// The navigation for one page: the whole tree as nested lists, linked relative to the
+/// page, with the page itself marked.
+fn nav(tree: &PageTree, layout: &Layout, current: &Path) -> String {
+    fn level(
+        tree: &PageTree,
+        layout: &Layout,
+        ids: &[Uuid],
+        current: &Path,
+        here: &Path,
+        out: &mut String,
+    ) {
+        out.push_str("<ul>\n");
+        for node in ids.iter().filter_map(|id| tree.get(id)) {
+            let Some(path) = layout.file(node.id()) else {
+                continue;
+            };
+            let marker = if path == current {
+                " aria-current=\"page\""
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "<li><a href=\"{}\"{marker}>{}</a>",
+                escape(&link_target(&relative_path(here, path))),
+                escape(node.page().title())
+            ));
+            if !node.children().is_empty() {
+                out.push('\n');
+                level(tree, layout, node.children(), current, here, out);
+            }
+            out.push_str("</li>\n");
+        }
+        out.push_str("</ul>\n");
+    }
+
+    let here = current.parent().unwrap_or(Path::new(""));
+    let home = link_target(&relative_path(here, Path::new("index.html")));
+    let mut out = format!(
+        "<a href=\"{}\">{}</a>\n",
+        escape(&home),
+        escape(tree.workspace().name())
+    );
+    level(tree, layout, tree.roots(), current, here, &mut out);
+    out
+}
+
+fn write_page(
+    dir: &Path,
+    path: &Path,
+    title: &str,
+    site_title: &str,
+    nav: &str,
+    body: &str,
+) -> NuclinoResult<()> {
+    let here = path.parent().unwrap_or(Path::new(""));
+    let stylesheet = link_target(&relative_path(here, Path::new(STYLESHEET_NAME)));
+    let title = if title == site_title {
+        escape(title)
+    } else {
+        format!("{} - {}", escape(title), escape(site_title))
+    };
+    let document = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+<link rel=\"stylesheet\" href=\"{}\">
+</head>
+<body>
+<nav>
+{nav}</nav>
+<main>
+{body}
+</main>
+</body>
+</html>
+",
+        escape(&stylesheet)
+    );
+    let full = dir.join(path);
+    if let Some(parent) = full.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(full, document)?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Client {
+    /// Render a workspace as a static HTML site in `dir`, with attachments. This fetches
+    /// every item and downloads every attachment, so it makes one request per item and
+    /// two per attachment.
+    pub fn render_workspace_site(
+        &self,
+        workspace: &Uuid,
+        dir: &Path,
+    ) -> NuclinoResult<Vec<SitePage>> {
+        let mut tree = self.workspace_tree(workspace)?;
+        tree.hydrate(self)?;
+        let attachments = self.tree_attachments(&tree, |_| true)?;
+        render_site(&tree, &attachments, dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        let deploy = format!(
+            "Read [the welcome](https://app.nuclino.com/t/b/{welcome}) or ask <https://app.nuclino.com/t/b/{welcome}>.\n\n\
+             ![diagram](https://files.nuclino.com/files/{file}/diagram.png)\n\n\
+             [Elsewhere](https://app.nuclino.com/t/b/{elsewhere})\n",
+            welcome = id(3),
+            file = id(10),
+            elsewhere = id(99),
+        );
+        let pages = vec![
+            collection(id(1), "Runbooks & <Guides>", &[id(2)]),
+            item(id(2), "Deploy", Some(&deploy)),
+            item(id(3), "index", Some("| a | b |\n|---|---|\n| 1 | 2 |")),
+        ];
+        PageTree::build(workspace(&[id(1), id(3)]), pages).expect("sample tree should build")
+    }
+
+    #[test]
+    fn rendering() {
+        let dir = std::env::temp_dir().join(format!("nuclino-site-{}", std::process::id()));
+        let mut attachments = HashMap::new();
+        attachments.insert(
+            id(2),
+            vec![(file(id(10), id(2), "diagram.png"), vec![1, 2, 3])],
+        );
+        let written = render_site(&sample(), &attachments, &dir).expect("rendering should succeed");
+        let paths: Vec<&Path> = written.iter().map(|p| p.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("Runbooks & -Guides-/index.html"),
+                Path::new("Runbooks & -Guides-/Deploy.html"),
+                // The front page has the root's index.html.
+                Path::new("index (2).html"),
+            ]
+        );
+        assert_eq!(
+            written[1].attachments,
+            vec![PathBuf::from(
+                "Runbooks & -Guides-/Deploy_files/diagram.png"
+            )]
+        );
+        assert_eq!(
+            std::fs::read(dir.join(&written[1].attachments[0]))
+                .expect("attachment should be copied"),
+            vec![1, 2, 3]
+        );
+
+        let deploy =
+            std::fs::read_to_string(dir.join(&written[1].path)).expect("page should exist");
+        assert!(deploy.contains("<title>Deploy - General</title>"));
+        assert!(deploy.contains("href=\"../style.css\""));
+        assert!(deploy.contains("<a href=\"../index%20%282%29.html\">the welcome</a>"));
+        assert!(deploy.contains("ask <a href=\"../index%20%282%29.html\">index</a>."));
+        assert!(deploy.contains("<img src=\"Deploy_files/diagram.png\" alt=\"diagram\" />"));
+        assert!(deploy.contains(&format!("https://app.nuclino.com/t/b/{}", id(99))));
+        assert!(deploy.contains("<a href=\"Deploy.html\" aria-current=\"page\">Deploy</a>"));
+        assert!(deploy.contains("Runbooks &amp; &lt;Guides&gt;"));
+
+        let home =
+            std::fs::read_to_string(dir.join("index.html")).expect("front page should exist");
+        assert!(home.contains("<h1>General</h1>"));
+        assert!(home.contains("<a href=\"Runbooks%20&amp;%20-Guides-/index.html\">"));
+        let table = std::fs::read_to_string(dir.join("index (2).html")).expect("page should exist");
+        assert!(table.contains("<table>"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn reserved_names() {
+        let dir =
+            std::env::temp_dir().join(format!("nuclino-site-reserved-{}", std::process::id()));
+        let pages = vec![
+            collection(id(1), "style.css", &[]),
+            item(id(2), "Notes", Some("Hello.")),
+            collection(id(3), "Notes_files", &[]),
+        ];
+        let tree =
+            PageTree::build(workspace(&[id(1), id(2), id(3)]), pages).expect("tree should build");
+        let mut attachments = HashMap::new();
+        attachments.insert(id(2), vec![(file(id(10), id(2), "a.png"), vec![1])]);
+        let written = render_site(&tree, &attachments, &dir).expect("rendering should succeed");
+        std::fs::remove_dir_all(&dir).ok();
+        let paths: Vec<&Path> = written.iter().map(|p| p.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("style.css (2)/index.html"),
+                Path::new("Notes.html"),
+                Path::new("Notes_files (2)/index.html"),
+            ]
+        );
+        assert_eq!(
+            written[1].attachments,
+            vec![PathBuf::from("Notes_files/a.png")]
+        );
+    }
+}
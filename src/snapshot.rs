@@ -0,0 +1,389 @@
+//! Point-in-time snapshots of a workspace, and structured diffs between two of them.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use uuid::Uuid;
+
+use crate::diff::{diff_lines, format_hunks, DiffLine};
+use crate::frontmatter::FrontMatter;
+use crate::sync::read_local;
+use crate::{Manifest, NuclinoResult, Page, PageKind, PageTree};
+
+/// What a snapshot records about one page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPage {
+    /// The page's id.
+    pub id: Uuid,
+    /// The page's title.
+    pub title: String,
+    /// Whether the page is an item or a collection.
+    pub kind: PageKind,
+    /// The collection containing the page, or `None` for top-level pages.
+    pub parent: Option<Uuid>,
+    /// The page's Markdown content. `None` for collections, and for items in snapshots
+    /// made from unhydrated trees.
+    pub content: Option<String>,
+    /// The page's last-modified time.
+    pub modified: String,
+}
+
+/// The pages of a workspace at some moment: their titles, places in the tree, and content.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    order: Vec<Uuid>,
+    pages: HashMap<Uuid, SnapshotPage>,
+}
+
+impl Snapshot {
+    /// Make a snapshot of a tree. Hydrate the tree first if you want content diffs.
+    pub fn from_tree(tree: &PageTree) -> Self {
+        let mut snapshot = Snapshot::default();
+        for node in tree.depth_first() {
+            let page = node.page();
+            let (kind, content) = match page {
+                Page::Item(item) => (PageKind::Item, item.content().cloned()),
+                Page::Collection(_) => (PageKind::Collection, None),
+            };
+            snapshot.push(SnapshotPage {
+                id: *page.id(),
+                title: page.title().to_string(),
+                kind,
+                parent: node.parent().copied(),
+                content,
+                modified: page.modified().to_string(),
+            });
+        }
+        snapshot
+    }
+
+    /// Make a snapshot from a backup archive.
+    #[cfg(feature = "backup")]
+    pub fn from_backup(backup: &crate::Backup) -> NuclinoResult<Self> {
+        Ok(Snapshot::from_tree(&backup.tree()?))
+    }
+
+    /// Make a snapshot from a directory written by `export_tree()`, using its manifest to
+    /// find the pages. Titles come from each file's front matter, and parents from the
+    /// directory structure.
+    pub fn from_export(dir: &Path) -> NuclinoResult<Self> {
+        let manifest = Manifest::load(dir)?;
+        let mut snapshot = Snapshot::default();
+        for (path, entry) in manifest.entries() {
+            let parent = path
+                .rsplit_once('/')
+                .and_then(|(parent_dir, _)| manifest.get(parent_dir))
+                .map(|parent| parent.id);
+            let file = match entry.kind {
+                PageKind::Item => dir.join(path),
+                PageKind::Collection => dir.join(path).join(format!("{}.md", crate::INDEX_NAME)),
+            };
+            let Ok(text) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            let (matter, _) = FrontMatter::split(&text);
+            let modified = matter
+                .as_ref()
+                .and_then(|m| m.get_str("modified"))
+                .map(str::to_string)
+                .or_else(|| entry.modified.clone())
+                .unwrap_or_default();
+            let (title, content) = read_local(&text);
+            let fallback = path.rsplit('/').next().unwrap_or(path.as_str());
+            snapshot.push(SnapshotPage {
+                id: entry.id,
+                title: title.unwrap_or_else(|| fallback.trim_end_matches(".md").to_string()),
+                kind: entry.kind.clone(),
+                parent,
+                content: match entry.kind {
+                    PageKind::Item => Some(content),
+                    PageKind::Collection => None,
+                },
+                modified,
+            });
+        }
+        Ok(snapshot)
+    }
+
+    fn push(&mut self, page: SnapshotPage) {
+        self.order.push(page.id);
+        self.pages.insert(page.id, page);
+    }
+
+    /// Look up a page in the snapshot.
+    pub fn get(&self, id: &Uuid) -> Option<&SnapshotPage> {
+        self.pages.get(id)
+    }
+
+    /// Every page in the snapshot, in the order they were added.
+    pub fn pages(&self) -> impl Iterator<Item = &SnapshotPage> {
+        self.order.iter().filter_map(|id| self.pages.get(id))
+    }
+
+    /// Compare this snapshot with a newer one. Changes are listed in the newer snapshot's
+    /// page order, followed by deletions in this snapshot's order. A page that was both
+    /// moved and edited produces one change for each.
+    pub fn diff(&self, newer: &Snapshot) -> SnapshotDiff {
+        let mut changes: Vec<PageChange> = Vec::new();
+        let parent_title = |snapshot: &Snapshot, parent: Option<Uuid>| {
+            parent
+                .and_then(|p| snapshot.get(&p))
+                .map(|p| p.title.clone())
+        };
+
+        for page in newer.pages() {
+            let Some(old) = self.get(&page.id) else {
+                changes.push(PageChange::Added {
+                    id: page.id,
+                    title: page.title.clone(),
+                    parent: parent_title(newer, page.parent),
+                });
+                continue;
+            };
+            if old.parent != page.parent {
+                changes.push(PageChange::Moved {
+                    id: page.id,
+                    title: page.title.clone(),
+                    from: parent_title(self, old.parent),
+                    to: parent_title(newer, page.parent),
+                });
+            }
+            if old.title != page.title {
+                changes.push(PageChange::Renamed {
+                    id: page.id,
+                    from: old.title.clone(),
+                    to: page.title.clone(),
+                });
+            }
+            if let (Some(before), Some(after)) = (old.content.as_ref(), page.content.as_ref()) {
+                // Exports always end content with a newline, so don't count that as a change.
+                if before.trim_end() != after.trim_end() {
+                    changes.push(PageChange::ContentChanged {
+                        id: page.id,
+                        title: page.title.clone(),
+                        diff: diff_lines(before, after),
+                    });
+                }
+            }
+        }
+        for page in self.pages() {
+            if newer.get(&page.id).is_none() {
+                changes.push(PageChange::Deleted {
+                    id: page.id,
+                    title: page.title.clone(),
+                });
+            }
+        }
+        SnapshotDiff { changes }
+    }
+}
+
+/// One difference between two snapshots. Parents are given by title, with `None`
+/// meaning the top level of the workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageChange {
+    /// A page exists only in the newer snapshot.
+    Added {
+        /// the page's id
+        id: Uuid,
+        /// the page's title
+        title: String,
+        /// the title of the collection it was added to
+        parent: Option<String>,
+    },
+    /// A page exists only in the older snapshot.
+    Deleted {
+        /// the page's id
+        id: Uuid,
+        /// the page's title
+        title: String,
+    },
+    /// A page is in a different collection.
+    Moved {
+        /// the page's id
+        id: Uuid,
+        /// the page's current title
+        title: String,
+        /// the title of the collection it was in
+        from: Option<String>,
+        /// the title of the collection it is in now
+        to: Option<String>,
+    },
+    /// A page's title changed.
+    Renamed {
+        /// the page's id
+        id: Uuid,
+        /// the old title
+        from: String,
+        /// the new title
+        to: String,
+    },
+    /// An item's Markdown content changed.
+    ContentChanged {
+        /// the page's id
+        id: Uuid,
+        /// the page's current title
+        title: String,
+        /// a line-level diff of the content
+        diff: Vec<DiffLine>,
+    },
+}
+
+impl PageChange {
+    /// The id of the page that changed.
+    pub fn id(&self) -> &Uuid {
+        match self {
+            PageChange::Added { id, .. }
+            | PageChange::Deleted { id, .. }
+            | PageChange::Moved { id, .. }
+            | PageChange::Renamed { id, .. }
+            | PageChange::ContentChanged { id, .. } => id,
+        }
+    }
+}
+
+/// Everything that changed between two snapshots. Its `Display` implementation is a
+/// human-readable report.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    changes: Vec<PageChange>,
+}
+
+impl SnapshotDiff {
+    /// Every change, in the order described by `Snapshot::diff()`.
+    pub fn changes(&self) -> &[PageChange] {
+        self.changes.as_slice()
+    }
+
+    /// True if the snapshots were the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn count(&self, matcher: fn(&PageChange) -> bool) -> usize {
+        self.changes.iter().filter(|c| matcher(c)).count()
+    }
+}
+
+fn location(parent: &Option<String>) -> &str {
+    parent.as_deref().unwrap_or("(top level)")
+}
+
+impl Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+        writeln!(
+            f,
+            "{} added, {} deleted, {} moved, {} renamed, {} edited",
+            self.count(|c| matches!(c, PageChange::Added { .. })),
+            self.count(|c| matches!(c, PageChange::Deleted { .. })),
+            self.count(|c| matches!(c, PageChange::Moved { .. })),
+            self.count(|c| matches!(c, PageChange::Renamed { .. })),
+            self.count(|c| matches!(c, PageChange::ContentChanged { .. })),
+        )?;
+        for change in &self.changes {
+            match change {
+                PageChange::Added { title, parent, .. } => {
+                    writeln!(f, "\nAdded: {title} (in {})", location(parent))?
+                }
+                PageChange::Deleted { title, .. } => writeln!(f, "\nDeleted: {title}")?,
+                PageChange::Moved {
+                    title, from, to, ..
+                } => writeln!(
+                    f,
+                    "\nMoved: {title}, from {} to {}",
+                    location(from),
+                    location(to)
+                )?,
+                PageChange::Renamed { from, to, .. } => writeln!(f, "\nRenamed: {from} -> {to}")?,
+                PageChange::ContentChanged { title, diff, .. } => {
+                    writeln!(f, "\nEdited: {title}")?;
+                    for line in format_hunks(diff, 2).lines() {
+                        writeln!(f, "    {line}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export_tree;
+    use crate::fixtures::*;
+
+    fn before() -> PageTree {
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2), id(3)]),
+            item(id(2), "Deploy", Some("one\ntwo\n")),
+            item(id(3), "Rollback", Some("undo")),
+            collection(id(4), "Archive", &[]),
+            item(id(5), "Doomed", Some("bye")),
+        ];
+        PageTree::build(workspace(&[id(1), id(4), id(5)]), pages).expect("tree should build")
+    }
+
+    fn after() -> PageTree {
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2)]),
+            item(id(2), "Deploying", Some("one\n2\n")),
+            collection(id(4), "Archive", &[id(3)]),
+            item(id(3), "Rollback", Some("undo")),
+            item(id(6), "Fresh", Some("hi")),
+        ];
+        PageTree::build(workspace(&[id(1), id(4), id(6)]), pages).expect("tree should build")
+    }
+
+    #[test]
+    fn diffing_trees() {
+        let diff = Snapshot::from_tree(&before()).diff(&Snapshot::from_tree(&after()));
+        let changes = diff.changes();
+        assert_eq!(changes.len(), 5);
+        assert_eq!(
+            changes[0],
+            PageChange::Renamed {
+                id: id(2),
+                from: "Deploy".into(),
+                to: "Deploying".into()
+            }
+        );
+        assert!(
+            matches!(&changes[1], PageChange::ContentChanged { id: changed, .. } if *changed == id(2))
+        );
+        assert_eq!(
+            changes[2],
+            PageChange::Moved {
+                id: id(3),
+                title: "Rollback".into(),
+                from: Some("Runbooks".into()),
+                to: Some("Archive".into())
+            }
+        );
+        assert!(
+            matches!(&changes[3], PageChange::Added { title, parent: None, .. } if title == "Fresh")
+        );
+        assert!(matches!(&changes[4], PageChange::Deleted { title, .. } if title == "Doomed"));
+
+        let report = diff.to_string();
+        assert!(report.starts_with("1 added, 1 deleted, 1 moved, 1 renamed, 1 edited\n"));
+        assert!(report.contains("Moved: Rollback, from Runbooks to Archive\n"));
+        assert!(report.contains("    - two\n    + 2\n"));
+    }
+
+    #[test]
+    fn snapshots_from_exports() {
+        let dir = std::env::temp_dir().join(format!("nuclino-snapshot-{}", std::process::id()));
+        export_tree(&before(), &dir).expect("export should work");
+        let exported = Snapshot::from_export(&dir).expect("reading the export should work");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let original = Snapshot::from_tree(&before());
+        assert!(original.diff(&exported).is_empty());
+        assert_eq!(exported.get(&id(3)).and_then(|p| p.parent), Some(id(1)));
+    }
+}
@@ -0,0 +1,412 @@
+//! Two-way sync between an exported directory and Nuclino. A hash of each tracked file's
+//! title and content, and the page's last-modified time, are recorded in the directory's
+//! `Manifest` when it's exported or synced. On the next sync, a changed hash means the
+//! file was edited or renamed locally and a changed timestamp means the page was edited
+//! on Nuclino.
+
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::export::{item_body, item_document};
+use crate::frontmatter::FrontMatter;
+use crate::{
+    Client, Manifest, ManifestEntry, ModifyItem, NuclinoError, NuclinoResult, Page, PageKind,
+    Precondition,
+};
+
+/// A stable hash of some text, as a hex string. This is 64-bit FNV-1a: fast, with
+/// no dependencies, and more than good enough for noticing that content changed.
+/// It isn't suitable for anything security-related.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// The hash sync records for a page: a `content_hash()` of its title and exported body
+/// together, so a rename counts as a change just as an edit does.
+pub(crate) fn synced_hash(title: &str, body: &str) -> String {
+    content_hash(&format!("{title}\n{body}"))
+}
+
+/// What happened to one tracked file during a sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncStatus {
+    /// Neither side changed.
+    Unchanged,
+    /// The local file changed, and we sent it to Nuclino.
+    Pushed,
+    /// The page changed on Nuclino, and we wrote it to the local file.
+    Pulled,
+    /// Both sides changed. Nuclino's version was written to the path given, next to the
+    /// local file, and neither side was touched.
+    Conflict(PathBuf),
+    /// The manifest lists this file, but it's gone.
+    LocalMissing,
+    /// The manifest lists this page, but Nuclino says it doesn't exist.
+    RemoteMissing,
+    /// The manifest doesn't have a hash and timestamp for this file, which happens for
+    /// imported pages. Export the workspace to start syncing them.
+    NotTracked,
+}
+
+/// The result of syncing one tracked file.
+#[derive(Debug, Clone)]
+pub struct SyncOutcome {
+    /// The file's path, relative to the synced directory.
+    pub path: String,
+    /// The id of the page it's synced with.
+    pub id: Uuid,
+    /// What happened.
+    pub status: SyncStatus,
+}
+
+/// What to do with a file, given what changed since the last sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SyncAction {
+    Nothing,
+    Push,
+    Pull,
+    /// Neither side's title and content changed from the other's, so only the manifest
+    /// needs updating.
+    Record,
+    Conflict,
+}
+
+/// Decide what to do with a file. `local_hash` is the `synced_hash()` of the file's current
+/// title and content; `remote_modified` and `remote_hash` describe the page on Nuclino
+/// right now.
+pub(crate) fn decide(
+    recorded_hash: &str,
+    recorded_modified: &str,
+    local_hash: &str,
+    remote_modified: &str,
+    remote_hash: &str,
+) -> SyncAction {
+    let local_changed = local_hash != recorded_hash;
+    let remote_changed = remote_modified != recorded_modified;
+    match (local_changed, remote_changed) {
+        (false, false) => SyncAction::Nothing,
+        (true, false) => SyncAction::Push,
+        (false, true) if remote_hash == recorded_hash => SyncAction::Record,
+        (false, true) => SyncAction::Pull,
+        (true, true) if local_hash == remote_hash => SyncAction::Record,
+        (true, true) => SyncAction::Conflict,
+    }
+}
+
+/// Split an exported file into its front matter title, if any, and its content.
+pub(crate) fn read_local(text: &str) -> (Option<String>, String) {
+    let (matter, body) = FrontMatter::split(text);
+    let title = matter
+        .as_ref()
+        .and_then(|m| m.get_str("title"))
+        .map(str::to_string);
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    (title, body.to_string())
+}
+
+/// Write Nuclino's version of a page next to the local file, as `<file>.conflict`.
+fn write_conflict(local: &Path, remote: &Page) -> NuclinoResult<PathBuf> {
+    let mut conflict = local.to_path_buf().into_os_string();
+    conflict.push(".conflict");
+    let conflict = PathBuf::from(conflict);
+    std::fs::write(&conflict, item_document(remote))?;
+    Ok(conflict)
+}
+
+impl Client {
+    /// Sync every item tracked in a directory's manifest with Nuclino: push local edits,
+    /// pull remote edits, and report conflicts when both sides changed. The manifest is
+    /// updated as files sync. Collections, and files the manifest doesn't track, are
+    /// left alone.
+    pub fn sync_directory(&self, dir: &Path) -> NuclinoResult<Vec<SyncOutcome>> {
+        let mut manifest = Manifest::load(dir)?;
+        let tracked: Vec<(String, ManifestEntry)> = manifest
+            .entries()
+            .filter(|(_, entry)| entry.kind == PageKind::Item)
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        let mut outcomes: Vec<SyncOutcome> = Vec::with_capacity(tracked.len());
+
+        for (path, entry) in tracked {
+            let status = self.sync_file(dir, &path, &entry, &mut manifest)?;
+            manifest.save(dir)?;
+            outcomes.push(SyncOutcome {
+                path,
+                id: entry.id,
+                status,
+            });
+        }
+        Ok(outcomes)
+    }
+
+    fn sync_file(
+        &self,
+        dir: &Path,
+        path: &str,
+        entry: &ManifestEntry,
+        manifest: &mut Manifest,
+    ) -> NuclinoResult<SyncStatus> {
+        let (Some(recorded_hash), Some(recorded_modified)) =
+            (entry.hash.as_ref(), entry.modified.as_ref())
+        else {
+            return Ok(SyncStatus::NotTracked);
+        };
+        let full = dir.join(path);
+        if !full.exists() {
+            return Ok(SyncStatus::LocalMissing);
+        }
+        let (title, local) = read_local(&std::fs::read_to_string(&full)?);
+
+        let remote = match self.page(&entry.id) {
+            Ok(page) => page,
+            Err(e) if e.is_not_found() => return Ok(SyncStatus::RemoteMissing),
+            Err(e) => return Err(e),
+        };
+        let remote_content = match &remote {
+            Page::Item(item) => item_body(item.content().map(String::as_str)),
+            Page::Collection(_) => return Ok(SyncStatus::NotTracked),
+        };
+        let remote_hash = synced_hash(remote.title(), &remote_content);
+        // A file without a title in its front matter leaves the title alone.
+        let local_hash = synced_hash(title.as_deref().unwrap_or(remote.title()), &local);
+
+        let record = |manifest: &mut Manifest, hash: String, modified: &str| {
+            manifest.insert(
+                path,
+                ManifestEntry {
+                    hash: Some(hash),
+                    modified: Some(modified.to_string()),
+                    ..entry.clone()
+                },
+            );
+        };
+
+        match decide(
+            recorded_hash,
+            recorded_modified,
+            &local_hash,
+            remote.modified(),
+            &remote_hash,
+        ) {
+            SyncAction::Nothing => Ok(SyncStatus::Unchanged),
+            SyncAction::Record => {
+                if title.as_deref() != Some(remote.title()) {
+                    std::fs::write(&full, item_document(&remote))?;
+                }
+                record(manifest, remote_hash, remote.modified());
+                Ok(SyncStatus::Unchanged)
+            }
+            SyncAction::Push => {
+                // The page hasn't changed since the last sync, so its title is the one we
+                // recorded; only send ours if it was renamed here.
+                let changes = ModifyItem {
+                    title: title.filter(|title| title != remote.title()),
+                    content: Some(local),
+                };
+                // Someone could edit the page between our read and this write.
+                let expected = Precondition::ModifiedAt(remote.modified().to_string());
+                let updated = match self.page_update_if_unmodified(&entry.id, &expected, &changes) {
+                    Ok(page) => page,
+                    Err(NuclinoError::Conflict(current)) => {
+                        return Ok(SyncStatus::Conflict(write_conflict(&full, &current)?))
+                    }
+                    Err(e) => return Err(e),
+                };
+                // Rewrite the front matter so it reflects the new modification time.
+                std::fs::write(&full, item_document(&updated))?;
+                let written = read_local(&std::fs::read_to_string(&full)?).1;
+                record(
+                    manifest,
+                    synced_hash(updated.title(), &written),
+                    updated.modified(),
+                );
+                Ok(SyncStatus::Pushed)
+            }
+            SyncAction::Pull => {
+                std::fs::write(&full, item_document(&remote))?;
+                record(manifest, remote_hash, remote.modified());
+                Ok(SyncStatus::Pulled)
+            }
+            SyncAction::Conflict => Ok(SyncStatus::Conflict(write_conflict(&full, &remote)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn hashing() {
+        // Reference values for 64-bit FNV-1a.
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(content_hash("# Deploy\n"), content_hash("# Deploy"));
+    }
+
+    #[test]
+    fn decisions() {
+        let old = "2021-12-15T17:02:53.487Z";
+        let new = "2022-01-01T00:00:00.000Z";
+        assert_eq!(decide("a", old, "a", old, "a"), SyncAction::Nothing);
+        assert_eq!(decide("a", old, "b", old, "a"), SyncAction::Push);
+        assert_eq!(decide("a", old, "a", new, "c"), SyncAction::Pull);
+        assert_eq!(decide("a", old, "a", new, "a"), SyncAction::Record);
+        assert_eq!(decide("a", old, "b", new, "c"), SyncAction::Conflict);
+        assert_eq!(decide("a", old, "b", new, "b"), SyncAction::Record);
+    }
+
+    #[test]
+    fn reading_exported_files() {
+        let (title, body) = read_local("---\ntitle: \"Deploy\"\n---\n\n# Steps\n");
+        assert_eq!(title.as_deref(), Some("Deploy"));
+        assert_eq!(body, "# Steps\n");
+        let (title, body) = read_local("no front matter\n");
+        assert!(title.is_none());
+        assert_eq!(body, "no front matter\n");
+    }
+
+    #[test]
+    fn remotely_deleted_pages() {
+        let root = std::env::temp_dir().join(format!("nuclino-sync-{}", std::process::id()));
+        std::fs::create_dir_all(&root).expect("creating a temp dir should work");
+        std::fs::write(root.join("Deploy.md"), "# Steps\n").expect("writing should work");
+        let entry = ManifestEntry {
+            id: id(1),
+            kind: PageKind::Item,
+            hash: Some(content_hash("# Steps\n")),
+            modified: Some("2021-12-15T17:02:53.487Z".to_string()),
+        };
+        let mut manifest = Manifest::default();
+        manifest.insert("Deploy.md", entry.clone());
+        manifest.save(&root).expect("saving should work");
+
+        let (url, server) = serve(vec![not_found()]);
+        let outcomes = local_client(&url)
+            .sync_directory(&root)
+            .expect("a deleted page shouldn't stop the sync");
+        let manifest = Manifest::load(&root).expect("loading should work");
+        std::fs::remove_dir_all(&root).ok();
+        server.join().expect("the server should finish");
+
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].status, SyncStatus::RemoteMissing);
+        assert_eq!(manifest.get("Deploy.md"), Some(&entry));
+    }
+
+    fn success(page: &Page) -> (u16, serde_json::Value) {
+        (200, json!({ "status": "success", "data": page }))
+    }
+
+    /// Export `page` into a fresh directory, as `Deploy.md`, tracked in its manifest.
+    fn tracked(name: &str, page: &Page) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("nuclino-sync-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&root).expect("creating a temp dir should work");
+        let document = item_document(page);
+        std::fs::write(root.join("Deploy.md"), &document).expect("writing should work");
+        let mut manifest = Manifest::default();
+        manifest.insert(
+            "Deploy.md",
+            ManifestEntry {
+                id: *page.id(),
+                kind: PageKind::Item,
+                hash: Some(synced_hash(page.title(), &read_local(&document).1)),
+                modified: Some(page.modified().to_string()),
+            },
+        );
+        manifest.save(&root).expect("saving should work");
+        root
+    }
+
+    #[test]
+    fn remote_renames() {
+        let old = "2021-12-15T17:02:53.487Z";
+        let renamed_at = "2022-01-01T00:00:00.000Z";
+        let edited_at = "2022-01-02T00:00:00.000Z";
+        let original = item_full(id(1), "Deploy", Some("# Steps\n"), old, &[], &[]);
+        let renamed = item_full(id(1), "Deploy v2", Some("# Steps\n"), renamed_at, &[], &[]);
+        let edited = item_full(
+            id(1),
+            "Deploy v2",
+            Some("# More steps\n"),
+            edited_at,
+            &[],
+            &[],
+        );
+        let root = tracked("remote-rename", &original);
+
+        // Renaming the page on Nuclino pulls the new title into the file.
+        let (url, server) = serve(vec![success(&renamed)]);
+        let outcomes = local_client(&url)
+            .sync_directory(&root)
+            .expect("syncing should work");
+        server.join().expect("the server should finish");
+        assert_eq!(outcomes[0].status, SyncStatus::Pulled);
+        let file = root.join("Deploy.md");
+        let text = std::fs::read_to_string(&file).expect("reading should work");
+        assert_eq!(read_local(&text).0.as_deref(), Some("Deploy v2"));
+
+        // Then a local edit pushes the content without touching the title.
+        std::fs::write(&file, text.replace("# Steps", "# More steps")).expect("writing");
+        let (url, server) =
+            serve_capturing(vec![success(&renamed), success(&renamed), success(&edited)]);
+        let outcomes = local_client(&url)
+            .sync_directory(&root)
+            .expect("syncing should work");
+        let requests = server.join().expect("the server should finish");
+        std::fs::remove_dir_all(&root).ok();
+        assert_eq!(outcomes[0].status, SyncStatus::Pushed);
+        let (line, body) = &requests[2];
+        assert!(line.starts_with("PUT "), "{line}");
+        let sent: serde_json::Value = serde_json::from_str(body).expect("the body is json");
+        assert_eq!(sent, json!({ "content": "# More steps\n" }));
+    }
+
+    #[test]
+    fn local_renames() {
+        let page = item(id(1), "Deploy", Some("# Steps\n"));
+        let root = tracked("local-rename", &page);
+        let file = root.join("Deploy.md");
+        let text = std::fs::read_to_string(&file).expect("reading should work");
+        std::fs::write(
+            &file,
+            text.replace("title: \"Deploy\"", "title: \"Ship it\""),
+        )
+        .expect("writing should work");
+
+        let renamed = item_full(
+            id(1),
+            "Ship it",
+            Some("# Steps\n"),
+            "2022-01-01T00:00:00.000Z",
+            &[],
+            &[],
+        );
+        let (url, server) =
+            serve_capturing(vec![success(&page), success(&page), success(&renamed)]);
+        let outcomes = local_client(&url)
+            .sync_directory(&root)
+            .expect("syncing should work");
+        let requests = server.join().expect("the server should finish");
+        let manifest = Manifest::load(&root).expect("loading should work");
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(outcomes[0].status, SyncStatus::Pushed);
+        let sent: serde_json::Value =
+            serde_json::from_str(&requests[2].1).expect("the body is json");
+        assert_eq!(sent, json!({ "title": "Ship it", "content": "# Steps\n" }));
+        assert_eq!(
+            manifest.get("Deploy.md").and_then(|e| e.hash.clone()),
+            Some(synced_hash("Ship it", "# Steps\n"))
+        );
+    }
+}
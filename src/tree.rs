@@ -0,0 +1,362 @@
+//! An in-memory model of a workspace's page hierarchy, built from the `child_ids`
+//! of the workspace and its collections.
+
+use std::collections::{HashMap, VecDeque};
+
+use uuid::Uuid;
+
+use crate::{Client, NuclinoError, NuclinoResult, Page, Workspace};
+
+/// One page in a `PageTree`, with its position in the hierarchy.
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    page: Page,
+    parent: Option<Uuid>,
+    depth: usize,
+    index: usize,
+    children: Vec<Uuid>,
+}
+
+impl TreeNode {
+    /// The id of this node's page.
+    pub fn id(&self) -> &Uuid {
+        self.page.id()
+    }
+
+    /// The page metadata for this node.
+    pub fn page(&self) -> &Page {
+        &self.page
+    }
+
+    /// The collection containing this page, or `None` if it's at the top level of the workspace.
+    pub fn parent(&self) -> Option<&Uuid> {
+        self.parent.as_ref()
+    }
+
+    /// How deep in the tree this page is. Top-level pages have depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// This page's position among its siblings, starting at 0.
+    pub fn sibling_index(&self) -> usize {
+        self.index
+    }
+
+    /// Ids of this page's children, in order. Always empty for items.
+    pub fn children(&self) -> &[Uuid] {
+        self.children.as_slice()
+    }
+}
+
+/// A workspace's pages arranged as the tree Nuclino shows in its sidebar.
+#[derive(Debug, Clone)]
+pub struct PageTree {
+    workspace: Workspace,
+    roots: Vec<Uuid>,
+    nodes: HashMap<Uuid, TreeNode>,
+    missing: Vec<Uuid>,
+}
+
+impl PageTree {
+    /// Arrange a workspace's pages into a tree. `pages` should be every page in the
+    /// workspace, as returned by `Client::workspace_pages()`. Children we don't have a
+    /// page for are skipped and reported by `missing()`; pages that aren't reachable
+    /// from the workspace are left out. Returns an error if a collection contains
+    /// itself or if a page has more than one parent.
+    pub fn build(workspace: Workspace, pages: Vec<Page>) -> NuclinoResult<Self> {
+        let mut unplaced: HashMap<Uuid, Page> = pages.into_iter().map(|p| (*p.id(), p)).collect();
+        let mut nodes: HashMap<Uuid, TreeNode> = HashMap::new();
+        let mut missing: Vec<Uuid> = Vec::new();
+        let workspace_id = *workspace.id();
+
+        let roots = place_children(
+            workspace.children(),
+            None,
+            0,
+            &workspace_id,
+            &mut unplaced,
+            &mut nodes,
+            &mut missing,
+        )?;
+        // Walk depth-first with an explicit stack so deep trees don't blow ours.
+        let mut stack: Vec<Uuid> = roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let Some(node) = nodes.get(&id) else {
+                return Err(NuclinoError::ProgrammerError);
+            };
+            let Page::Collection(collection) = &node.page else {
+                continue;
+            };
+            let child_ids = collection.children().to_vec();
+            let depth = node.depth + 1;
+            let placed = place_children(
+                &child_ids,
+                Some(id),
+                depth,
+                &workspace_id,
+                &mut unplaced,
+                &mut nodes,
+                &mut missing,
+            )?;
+            stack.extend(placed.iter().rev());
+            if let Some(node) = nodes.get_mut(&id) {
+                node.children = placed;
+            }
+        }
+
+        Ok(PageTree {
+            workspace,
+            roots,
+            nodes,
+            missing,
+        })
+    }
+
+    /// The workspace this tree describes.
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspace
+    }
+
+    /// Ids of the top-level pages of the workspace, in order.
+    pub fn roots(&self) -> &[Uuid] {
+        self.roots.as_slice()
+    }
+
+    /// Look up a single node by page id.
+    pub fn get(&self, id: &Uuid) -> Option<&TreeNode> {
+        self.nodes.get(id)
+    }
+
+    /// The node for the collection containing this page, if it isn't top-level.
+    pub fn parent_of(&self, id: &Uuid) -> Option<&TreeNode> {
+        self.nodes.get(id)?.parent().and_then(|p| self.nodes.get(p))
+    }
+
+    /// Ids that appear in some `child_ids` list but weren't among the pages provided.
+    pub fn missing(&self) -> &[Uuid] {
+        self.missing.as_slice()
+    }
+
+    /// How many pages are in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// True if the workspace has no pages.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Iterate over the tree depth-first, visiting each collection before its children.
+    /// This is the order pages appear in the Nuclino sidebar with everything expanded.
+    pub fn depth_first(&self) -> DepthFirst<'_> {
+        DepthFirst {
+            tree: self,
+            stack: self.roots.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Iterate over the tree breadth-first: all top-level pages, then everything at depth 1, and so on.
+    pub fn breadth_first(&self) -> BreadthFirst<'_> {
+        BreadthFirst {
+            tree: self,
+            queue: self.roots.iter().copied().collect(),
+        }
+    }
+
+    /// Replace the metadata for a page already in the tree, for instance with a fully-fetched
+    /// copy that includes content. Returns false if the page isn't in the tree.
+    pub fn replace_page(&mut self, page: Page) -> bool {
+        match self.nodes.get_mut(page.id()) {
+            Some(node) => {
+                node.page = page;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fetch every item in the tree by id, so each node has its page content. The workspace
+    /// listing that `PageTree`s are built from doesn't include content.
+    pub fn hydrate(&mut self, client: &Client) -> NuclinoResult<()> {
+        let item_ids: Vec<Uuid> = self
+            .depth_first()
+            .filter(|node| matches!(node.page(), Page::Item(_)))
+            .map(|node| *node.id())
+            .collect();
+        for id in item_ids {
+            let page = client.page(&id)?;
+            self.replace_page(page);
+        }
+        Ok(())
+    }
+}
+
+/// Turn a list of child ids into tree nodes, checking for pages we've already placed.
+fn place_children(
+    child_ids: &[Uuid],
+    parent: Option<Uuid>,
+    depth: usize,
+    workspace_id: &Uuid,
+    unplaced: &mut HashMap<Uuid, Page>,
+    nodes: &mut HashMap<Uuid, TreeNode>,
+    missing: &mut Vec<Uuid>,
+) -> NuclinoResult<Vec<Uuid>> {
+    let mut placed: Vec<Uuid> = Vec::with_capacity(child_ids.len());
+    for child in child_ids {
+        if let Some(existing) = nodes.get(child) {
+            // Either the child is one of our ancestors, or it has two parents.
+            let mut ancestor = parent;
+            while let Some(id) = ancestor {
+                if id == *child {
+                    return Err(NuclinoError::TreeCycle(*child));
+                }
+                ancestor = nodes.get(&id).and_then(|n| n.parent);
+            }
+            return Err(NuclinoError::DuplicateParent {
+                child: *child,
+                first: existing.parent.unwrap_or(*workspace_id),
+                second: parent.unwrap_or(*workspace_id),
+            });
+        }
+        let Some(page) = unplaced.remove(child) else {
+            missing.push(*child);
+            continue;
+        };
+        nodes.insert(
+            *child,
+            TreeNode {
+                page,
+                parent,
+                depth,
+                index: placed.len(),
+                children: Vec::new(),
+            },
+        );
+        placed.push(*child);
+    }
+    Ok(placed)
+}
+
+/// A depth-first iterator over a `PageTree`. See `PageTree::depth_first()`.
+#[derive(Debug)]
+pub struct DepthFirst<'a> {
+    tree: &'a PageTree,
+    stack: Vec<Uuid>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = &'a TreeNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        let node = self.tree.nodes.get(&id)?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+/// A breadth-first iterator over a `PageTree`. See `PageTree::breadth_first()`.
+#[derive(Debug)]
+pub struct BreadthFirst<'a> {
+    tree: &'a PageTree,
+    queue: VecDeque<Uuid>,
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = &'a TreeNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.queue.pop_front()?;
+        let node = self.tree.nodes.get(&id)?;
+        self.queue.extend(node.children.iter());
+        Some(node)
+    }
+}
+
+impl Client {
+    /// Fetch a workspace and all of its pages, and arrange them into a `PageTree`.
+    /// Pages in the tree don't have content; call `hydrate()` on the tree if you need it.
+    pub fn workspace_tree(&self, workspace: &Uuid) -> NuclinoResult<PageTree> {
+        let space = self.workspace(workspace)?;
+        let pages = self.workspace_pages(workspace)?;
+        PageTree::build(space, pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    fn sample() -> PageTree {
+        // 1: Runbooks (collection) -> 2: Deploy, 3: Rollback
+        // 4: Welcome
+        // 5: Archive (collection) -> 6: Old (collection) -> 7: Ancient
+        let pages = vec![
+            collection(id(1), "Runbooks", &[id(2), id(3)]),
+            item(id(2), "Deploy", None),
+            item(id(3), "Rollback", None),
+            item(id(4), "Welcome", None),
+            collection(id(5), "Archive", &[id(6), id(99)]),
+            collection(id(6), "Old", &[id(7)]),
+            item(id(7), "Ancient", None),
+        ];
+        PageTree::build(workspace(&[id(1), id(4), id(5)]), pages).expect("sample tree should build")
+    }
+
+    #[test]
+    fn structure() {
+        let tree = sample();
+        assert_eq!(tree.len(), 7);
+        assert_eq!(tree.roots(), &[id(1), id(4), id(5)]);
+        assert_eq!(tree.missing(), &[id(99)]);
+
+        let ancient = tree.get(&id(7)).expect("page 7 should be in the tree");
+        assert_eq!(ancient.depth(), 2);
+        assert_eq!(ancient.parent(), Some(&id(6)));
+        assert_eq!(ancient.sibling_index(), 0);
+        let rollback = tree.get(&id(3)).expect("page 3 should be in the tree");
+        assert_eq!(rollback.sibling_index(), 1);
+        assert_eq!(
+            tree.parent_of(&id(3)).map(|n| n.page().title()),
+            Some("Runbooks")
+        );
+        assert!(tree.parent_of(&id(4)).is_none());
+    }
+
+    #[test]
+    fn traversal_orders() {
+        let tree = sample();
+        let depth: Vec<u128> = tree.depth_first().map(|n| n.id().as_u128()).collect();
+        assert_eq!(depth, vec![1, 2, 3, 4, 5, 6, 7]);
+        let breadth: Vec<u128> = tree.breadth_first().map(|n| n.id().as_u128()).collect();
+        assert_eq!(breadth, vec![1, 4, 5, 2, 3, 6, 7]);
+    }
+
+    #[test]
+    fn cycles() {
+        let pages = vec![
+            collection(id(1), "Outer", &[id(2)]),
+            collection(id(2), "Inner", &[id(1)]),
+        ];
+        let result = PageTree::build(workspace(&[id(1)]), pages);
+        assert!(matches!(result, Err(NuclinoError::TreeCycle(cycle)) if cycle == id(1)));
+    }
+
+    #[test]
+    fn duplicate_parents() {
+        let pages = vec![
+            collection(id(1), "One", &[id(3)]),
+            collection(id(2), "Two", &[id(3)]),
+            item(id(3), "Shared", None),
+        ];
+        let result = PageTree::build(workspace(&[id(1), id(2)]), pages);
+        assert!(matches!(
+            result,
+            Err(NuclinoError::DuplicateParent { child, first, second })
+                if child == id(3) && first == id(1) && second == id(2)
+        ));
+    }
+}
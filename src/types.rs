@@ -485,7 +485,8 @@ impl Item {
         &self.last_updated_user_id
     }
 
-    /// If this item is returned in a list of search results, the search string to highlight.
+    /// If this item is returned in a list of search results, the search string to highlight,
+    /// with the server's markup. `highlight()` parses it.
     pub fn highlight_text(&self) -> Option<&String> {
         self.highlight.as_ref()
     }
@@ -0,0 +1,164 @@
+//! Parsing the urls people paste from the Nuclino web app into ids, and back again.
+
+use url::Url;
+use uuid::Uuid;
+
+use crate::{Client, NuclinoError, NuclinoResult, Page, Team};
+
+/// The base url of the Nuclino web app, which is where page and team urls point.
+pub static APP_URL: &str = "https://app.nuclino.com";
+
+/// What a Nuclino web app url refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NuclinoUrl {
+    /// A page, either an item or a collection, identified by its id.
+    Page(Uuid),
+    /// A team, identified by the name in its url, e.g. `Team-One`. Team urls don't
+    /// include the team's id, so resolving one requires a lookup.
+    Team(String),
+}
+
+/// A Nuclino url resolved into the data it refers to.
+#[derive(Debug, Clone)]
+pub enum Resolved {
+    /// The url pointed to a page.
+    Page(Page),
+    /// The url pointed to a team.
+    Team(Team),
+}
+
+/// Parse a Nuclino web app url. Page urls come in two shapes: the canonical
+/// `https://app.nuclino.com/t/b/<uuid>` form the API returns, and the
+/// `https://app.nuclino.com/Team/Workspace/Title-<uuid>` form the app shows in its
+/// address bar. Both are recognized by the uuid at the end of the path. A url with a
+/// single path segment and no uuid is a team url.
+pub fn parse_url(input: &str) -> NuclinoResult<NuclinoUrl> {
+    let invalid = || NuclinoError::InvalidUrl(input.to_string());
+    let parsed = Url::parse(input.trim()).map_err(|_| invalid())?;
+    match parsed.host_str() {
+        Some(host) if host == "nuclino.com" || host.ends_with(".nuclino.com") => {}
+        _ => return Err(invalid()),
+    }
+
+    let segments: Vec<&str> = parsed
+        .path_segments()
+        .map(|segs| segs.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let Some(last) = segments.last() else {
+        return Err(invalid());
+    };
+    if let Some(id) = trailing_uuid(last) {
+        return Ok(NuclinoUrl::Page(id));
+    }
+    if segments.len() == 1 {
+        let team = urlencoding::decode(last).map_err(|_| invalid())?;
+        return Ok(NuclinoUrl::Team(team.into_owned()));
+    }
+    Err(invalid())
+}
+
+/// Build the canonical url for a page, in the same form `Page::url()` returns. The
+/// team supplies the app's scheme and host.
+pub fn page_url(team: &Team, id: &Uuid) -> String {
+    let base = Url::parse(team.url())
+        .ok()
+        .map(|url| url.origin().ascii_serialization())
+        .filter(|origin| origin.starts_with("http"))
+        .unwrap_or_else(|| APP_URL.to_string());
+    format!("{base}/t/b/{id}")
+}
+
+/// Pull a uuid off the end of a path segment like `Deploy-Runbook-<uuid>`.
+fn trailing_uuid(segment: &str) -> Option<Uuid> {
+    // A hyphenated uuid is always 36 bytes of ascii.
+    let start = segment.len().checked_sub(36)?;
+    let tail = segment.get(start..)?;
+    Uuid::try_parse(tail).ok()
+}
+
+impl NuclinoUrl {
+    /// The page id this url refers to, if it's a page url.
+    pub fn page_id(&self) -> Option<&Uuid> {
+        match self {
+            NuclinoUrl::Page(id) => Some(id),
+            NuclinoUrl::Team(_) => None,
+        }
+    }
+}
+
+impl Client {
+    /// Parse a Nuclino web app url and fetch the page or team it refers to.
+    /// Teams are found by matching their url against the teams this client can see.
+    pub fn resolve_url(&self, url: &str) -> NuclinoResult<Resolved> {
+        match parse_url(url)? {
+            NuclinoUrl::Page(id) => Ok(Resolved::Page(self.page(&id)?)),
+            NuclinoUrl::Team(name) => self
+                .all_teams()?
+                .into_iter()
+                .find(|team| {
+                    matches!(parse_url(team.url()), Ok(NuclinoUrl::Team(t)) if t.eq_ignore_ascii_case(&name))
+                })
+                .map(Resolved::Team)
+                .ok_or_else(|| NuclinoError::NotFound(format!("team {name}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::uuid;
+
+    use super::*;
+
+    #[test]
+    fn page_urls() {
+        let id = uuid!("aaf6d580-565d-497b-9ff3-b32075de3f4c");
+        let canonical =
+            parse_url("https://app.nuclino.com/t/b/aaf6d580-565d-497b-9ff3-b32075de3f4c")
+                .expect("the canonical form should parse");
+        assert_eq!(canonical, NuclinoUrl::Page(id));
+
+        let pretty = parse_url(
+            "https://app.nuclino.com/Team-One/General/My-Item-aaf6d580-565d-497b-9ff3-b32075de3f4c",
+        )
+        .expect("the address bar form should parse");
+        assert_eq!(pretty.page_id(), Some(&id));
+    }
+
+    #[test]
+    fn team_urls() {
+        let team = parse_url("https://app.nuclino.com/Team-One").expect("team urls should parse");
+        assert_eq!(team, NuclinoUrl::Team("Team-One".to_string()));
+        assert!(team.page_id().is_none());
+    }
+
+    #[test]
+    fn not_nuclino_urls() {
+        assert!(parse_url("https://example.com/t/b/aaf6d580-565d-497b-9ff3-b32075de3f4c").is_err());
+        assert!(parse_url("https://app.nuclino.com/").is_err());
+        assert!(parse_url("https://app.nuclino.com/Team-One/General").is_err());
+        assert!(parse_url("not a url at all").is_err());
+    }
+
+    #[test]
+    fn building_page_urls() {
+        let team: Team = serde_json::from_str(
+            r#"{
+                "object": "team",
+                "id": "020f9737-7b21-442b-85eb-bd420e5593b2",
+                "url": "https://app.nuclino.com/Team-One",
+                "name": "Team One",
+                "createdAt": "2021-10-21T09:34:47.885Z",
+                "createdUserId": "2e96f3bb-c742-4164-af2c-151ab2fd346b"
+            }"#,
+        )
+        .expect("the example team should deserialize");
+        let id = uuid!("aaf6d580-565d-497b-9ff3-b32075de3f4c");
+        let url = page_url(&team, &id);
+        assert_eq!(
+            url,
+            "https://app.nuclino.com/t/b/aaf6d580-565d-497b-9ff3-b32075de3f4c"
+        );
+        assert_eq!(parse_url(&url).expect("round trip"), NuclinoUrl::Page(id));
+    }
+}
@@ -0,0 +1,346 @@
+//! Polling a workspace for changes. Nuclino has no webhooks, so `ChangeWatcher` lists
+//! the workspace's pages periodically and compares each listing with the one before.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{Client, NuclinoResult, Page};
+
+/// How often a `ChangeWatcher` polls unless told otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A change to a page noticed by a `ChangeWatcher`. Pages carry the metadata from the
+/// listing where the change was seen, which doesn't include content. One page can
+/// produce several events in a single poll: a rename also updates the page, for instance.
+#[derive(Debug, Clone)]
+pub enum ChangeEvent {
+    /// A page appeared.
+    Created(Page),
+    /// A page's last-modified time changed.
+    Updated(Page),
+    /// A page disappeared, presumably into the trash.
+    Deleted {
+        /// the id of the deleted page
+        id: Uuid,
+        /// its title when we last saw it
+        title: String,
+    },
+    /// A page moved to a different collection. `None` means the top level of the workspace.
+    Moved {
+        /// the page as it is now
+        page: Page,
+        /// the collection it was in
+        from: Option<Uuid>,
+        /// the collection it is in now
+        to: Option<Uuid>,
+    },
+    /// A page's title changed.
+    Renamed {
+        /// the page as it is now
+        page: Page,
+        /// its previous title
+        from: String,
+    },
+}
+
+impl ChangeEvent {
+    /// The id of the page this event is about.
+    pub fn id(&self) -> &Uuid {
+        match self {
+            ChangeEvent::Created(page) | ChangeEvent::Updated(page) => page.id(),
+            ChangeEvent::Moved { page, .. } | ChangeEvent::Renamed { page, .. } => page.id(),
+            ChangeEvent::Deleted { id, .. } => id,
+        }
+    }
+}
+
+/// What we remember about a page between polls.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+struct PageState {
+    title: String,
+    modified: String,
+    parent: Option<Uuid>,
+}
+
+/// The persisted form of a watcher's memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Checkpoint {
+    workspace: Uuid,
+    pages: HashMap<Uuid, PageState>,
+}
+
+/// Work out what we know about each page from a listing. A page's parent is the
+/// collection listing it as a child; pages no collection claims are top-level.
+fn page_states(pages: &[Page]) -> HashMap<Uuid, PageState> {
+    let mut parents: HashMap<Uuid, Uuid> = HashMap::new();
+    for page in pages {
+        if let Page::Collection(collection) = page {
+            for child in collection.children() {
+                parents.insert(*child, *collection.id());
+            }
+        }
+    }
+    pages
+        .iter()
+        .map(|page| {
+            (
+                *page.id(),
+                PageState {
+                    title: page.title().to_string(),
+                    modified: page.modified().to_string(),
+                    parent: parents.get(page.id()).copied(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Compare a previous state with a fresh listing. Events come in listing order, with
+/// deletions last.
+fn changes_between(previous: &HashMap<Uuid, PageState>, pages: &[Page]) -> Vec<ChangeEvent> {
+    let current = page_states(pages);
+    let mut events: Vec<ChangeEvent> = Vec::new();
+    for page in pages {
+        let Some(now) = current.get(page.id()) else {
+            continue;
+        };
+        let Some(before) = previous.get(page.id()) else {
+            events.push(ChangeEvent::Created(page.clone()));
+            continue;
+        };
+        if before.modified != now.modified {
+            events.push(ChangeEvent::Updated(page.clone()));
+        }
+        if before.title != now.title {
+            events.push(ChangeEvent::Renamed {
+                page: page.clone(),
+                from: before.title.clone(),
+            });
+        }
+        if before.parent != now.parent {
+            events.push(ChangeEvent::Moved {
+                page: page.clone(),
+                from: before.parent,
+                to: now.parent,
+            });
+        }
+    }
+    let mut deleted: Vec<(&Uuid, &PageState)> = previous
+        .iter()
+        .filter(|(id, _)| !current.contains_key(id))
+        .collect();
+    deleted.sort_by(|a, b| a.1.title.cmp(&b.1.title));
+    events.extend(deleted.into_iter().map(|(id, state)| ChangeEvent::Deleted {
+        id: *id,
+        title: state.title.clone(),
+    }));
+    events
+}
+
+/// Watches a workspace for changes by polling. The first poll only records the
+/// workspace's current state, unless there's a checkpoint from an earlier run to
+/// compare with. Each later poll reports what changed since the one before.
+///
+/// ```no_run
+/// # fn main() -> nuclino_rs::NuclinoResult<()> {
+/// let client = nuclino_rs::Client::create_from_env()?;
+/// let workspace = nuclino_rs::Uuid::nil();
+/// nuclino_rs::ChangeWatcher::new(&client, &workspace)
+///     .checkpoint(std::path::Path::new("watcher.json"))
+///     .watch(|event| println!("{event:?}"))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChangeWatcher {
+    client: Client,
+    workspace: Uuid,
+    interval: Duration,
+    checkpoint_path: Option<PathBuf>,
+    state: Option<HashMap<Uuid, PageState>>,
+}
+
+impl ChangeWatcher {
+    /// Create a watcher for a workspace, polling every `DEFAULT_POLL_INTERVAL`.
+    pub fn new(client: &Client, workspace: &Uuid) -> Self {
+        ChangeWatcher {
+            client: client.clone(),
+            workspace: *workspace,
+            interval: DEFAULT_POLL_INTERVAL,
+            checkpoint_path: None,
+            state: None,
+        }
+    }
+
+    /// How long to wait between polls.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Persist the watcher's state to this file after every poll, and pick up from it if
+    /// it already exists, so that a restarted watcher doesn't replay old changes or miss
+    /// new ones. A checkpoint for some other workspace is ignored.
+    pub fn checkpoint(&mut self, path: &Path) -> &mut Self {
+        self.checkpoint_path = Some(path.to_path_buf());
+        self.state = None;
+        self
+    }
+
+    /// List the workspace once and return what changed since the last poll.
+    pub fn poll(&mut self) -> NuclinoResult<Vec<ChangeEvent>> {
+        if self.state.is_none() {
+            self.state = self.load_checkpoint()?;
+        }
+        let pages = self.client.workspace_pages(&self.workspace)?;
+        let events = match self.state.as_ref() {
+            Some(previous) => changes_between(previous, &pages),
+            None => Vec::new(),
+        };
+        self.state = Some(page_states(&pages));
+        self.save_checkpoint()?;
+        Ok(events)
+    }
+
+    /// Poll forever, calling `callback` with each change. Returns only if polling fails.
+    pub fn watch<F: FnMut(ChangeEvent)>(&mut self, mut callback: F) -> NuclinoResult<()> {
+        loop {
+            for event in self.poll()? {
+                callback(event);
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    /// Poll forever, sending each change down a channel. Returns `Ok` once the receiving
+    /// end of the channel is dropped, or an error if polling fails.
+    pub fn watch_channel(&mut self, sender: Sender<ChangeEvent>) -> NuclinoResult<()> {
+        loop {
+            for event in self.poll()? {
+                if sender.send(event).is_err() {
+                    return Ok(());
+                }
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    fn load_checkpoint(&self) -> NuclinoResult<Option<HashMap<Uuid, PageState>>> {
+        let Some(path) = self.checkpoint_path.as_ref() else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let checkpoint: Checkpoint = serde_json::from_slice(&std::fs::read(path)?)?;
+        if checkpoint.workspace != self.workspace {
+            return Ok(None);
+        }
+        Ok(Some(checkpoint.pages))
+    }
+
+    fn save_checkpoint(&self) -> NuclinoResult<()> {
+        let (Some(path), Some(pages)) = (self.checkpoint_path.as_ref(), self.state.as_ref()) else {
+            return Ok(());
+        };
+        let checkpoint = Checkpoint {
+            workspace: self.workspace,
+            pages: pages.clone(),
+        };
+        // Write then rename, so a crash mid-write doesn't leave a corrupt checkpoint.
+        let mut partial = path.clone().into_os_string();
+        partial.push(".tmp");
+        std::fs::write(&partial, serde_json::to_vec(&checkpoint)?)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::*;
+
+    #[test]
+    fn detecting_changes() {
+        let before = vec![
+            collection(id(1), "Runbooks", &[id(2)]),
+            item(id(2), "Deploy", None),
+            item(id(3), "Welcome", None),
+            item(id(4), "Doomed", None),
+        ];
+        let after = vec![
+            collection(id(1), "Runbooks", &[id(3)]),
+            item_full(
+                id(2),
+                "Deploying",
+                None,
+                "2022-01-01T00:00:00.000Z",
+                &[],
+                &[],
+            ),
+            item(id(3), "Welcome", None),
+            item(id(5), "Fresh", None),
+        ];
+        let events = changes_between(&page_states(&before), &after);
+        let summary: Vec<String> = events
+            .iter()
+            .map(|event| match event {
+                ChangeEvent::Created(page) => format!("created {}", page.title()),
+                ChangeEvent::Updated(page) => format!("updated {}", page.title()),
+                ChangeEvent::Deleted { title, .. } => format!("deleted {title}"),
+                ChangeEvent::Moved { page, from, to } => {
+                    format!("moved {} {:?} -> {:?}", page.title(), from, to)
+                }
+                ChangeEvent::Renamed { page, from } => {
+                    format!("renamed {from} -> {}", page.title())
+                }
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                "updated Deploying".to_string(),
+                "renamed Deploy -> Deploying".to_string(),
+                format!("moved Deploying Some({}) -> None", id(1)),
+                format!("moved Welcome None -> Some({})", id(1)),
+                "created Fresh".to_string(),
+                "deleted Doomed".to_string(),
+            ]
+        );
+        assert!(changes_between(&page_states(&after), &after).is_empty());
+    }
+
+    #[test]
+    fn checkpoints() {
+        let path = std::env::temp_dir().join(format!("nuclino-watch-{}.json", std::process::id()));
+        let client = Client::create("not-a-real-key", None);
+        let workspace = id(100);
+        let mut watcher = ChangeWatcher::new(&client, &workspace);
+        watcher.checkpoint(&path);
+        watcher.state = Some(page_states(&[item(id(2), "Deploy", None)]));
+        watcher
+            .save_checkpoint()
+            .expect("saving a checkpoint should work");
+
+        let mut restarted = ChangeWatcher::new(&client, &workspace);
+        restarted.checkpoint(&path);
+        let loaded = restarted
+            .load_checkpoint()
+            .expect("loading a checkpoint should work");
+        assert_eq!(loaded, watcher.state);
+
+        let mut elsewhere = ChangeWatcher::new(&client, &id(101));
+        elsewhere.checkpoint(&path);
+        let ignored = elsewhere
+            .load_checkpoint()
+            .expect("loading should still work");
+        std::fs::remove_file(&path).ok();
+        assert!(ignored.is_none());
+    }
+}